@@ -5,7 +5,20 @@ use termion::raw::IntoRawMode;
 use termion::{clear, cursor};
 
 // Import your game types
-use ctf_backend::game::{GameState, Move};
+use ctf_backend::game::{Fixed, GameRules, GameState, Move};
+
+// Every key press is one tick, so tick slowly enough for each press to move a
+// player visibly, and skip the countdowns between rounds
+fn new_game() -> GameState {
+    let mut game = GameState::new().with_rules(GameRules {
+        tick_rate: 5,
+        countdown_secs: Fixed::ZERO,
+        round_over_secs: Fixed::ZERO,
+        ..GameRules::default()
+    });
+    game.start();
+    game
+}
 
 fn main() {
     println!("🎮 Interactive Game Test");
@@ -20,7 +33,7 @@ fn main() {
     let _stdin = io::stdin();
     let mut _stdout = io::stdout().into_raw_mode().unwrap();
 
-    let mut game = GameState::<4>::new();
+    let mut game = new_game();
     print!("{}{}", clear::All, cursor::Goto(1, 1));
     game.pretty_print();
     io::stdout().flush().unwrap();
@@ -32,7 +45,7 @@ fn main() {
                 break;
             }
             Key::Char('r') => {
-                game = GameState::<4>::new();
+                game = new_game();
                 print!("{}{}", clear::All, cursor::Goto(1, 1));
                 println!("🔄 Game reset!");
                 game.pretty_print();
@@ -45,7 +58,7 @@ fn main() {
             }
             key => {
                 let moves = parse_key_to_moves(key);
-                game.step(moves);
+                game.step(&moves);
                 // Clear screen and reprint after each move
                 print!("{}{}", clear::All, cursor::Goto(1, 1));
                 game.pretty_print();
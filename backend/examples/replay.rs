@@ -0,0 +1,72 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::process;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{clear, cursor};
+
+use ctf_backend::game::{GameState, Replay};
+
+const FAST_FORWARD_TICKS: usize = 25;
+
+// Usage: cargo run --example replay -- <replay file> [start tick]
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: replay <replay file> [start tick]");
+        process::exit(2);
+    };
+    let start_tick: usize = args.next().and_then(|tick| tick.parse().ok()).unwrap_or(0);
+
+    let file = File::open(&path).unwrap_or_else(|err| {
+        eprintln!("could not open {path}: {err}");
+        process::exit(1);
+    });
+    let mut replay = GameState::replay(BufReader::new(file)).unwrap_or_else(|err| {
+        eprintln!("could not load {path}: {err}");
+        process::exit(1);
+    });
+    replay.seek(start_tick);
+
+    let _stdout = io::stdout().into_raw_mode().unwrap();
+    draw(&replay);
+
+    for key in io::stdin().keys() {
+        match key.unwrap() {
+            Key::Char('q') => break,
+            Key::Right | Key::Char('n') | Key::Char(' ') => {
+                replay.step();
+            }
+            Key::Left | Key::Char('b') => replay.seek(replay.tick().saturating_sub(1)),
+            Key::Char('f') => replay.seek(replay.tick() + FAST_FORWARD_TICKS),
+            Key::Char('r') => replay.seek(replay.tick().saturating_sub(FAST_FORWARD_TICKS)),
+            Key::Char('e') => replay.seek(replay.tick_count()),
+            Key::Char('0') => replay.seek(0),
+            _ => continue,
+        }
+        draw(&replay);
+    }
+}
+
+fn draw(replay: &Replay) {
+    print!("{}{}", clear::All, cursor::Goto(1, 1));
+    print!(
+        "Tick {}/{}  phase {:?}\r\n",
+        replay.tick(),
+        replay.tick_count(),
+        replay.game().phase()
+    );
+    replay.game().pretty_print();
+    match replay.matches_recording() {
+        Some(true) => print!("End of recording, final state matches\r\n"),
+        Some(false) => print!("End of recording, final state DIFFERS from the recording\r\n"),
+        None if replay.is_finished() => print!("End of recording\r\n"),
+        None => {}
+    }
+    print!(
+        "→/n/space: next tick  ←/b: previous tick  f/r: {FAST_FORWARD_TICKS} ticks forward/back  e: end  0: start  q: quit\r\n"
+    );
+    io::stdout().flush().unwrap();
+}
@@ -0,0 +1,1272 @@
+//! Each room runs as a single task, its actor, which owns the room's game and
+//! everything else in it. Connections talk to the actor through a [`Room`] handle,
+//! sending it commands over a channel, and hear from it on a broadcast channel, so
+//! rooms never wait on a shared lock.
+
+use crate::error::{Error, Result};
+use crate::game::{
+    GameEvent, GameRules, GameState, MAX_TEAM_SIZE, Map, MatchPhase, Move, ReplayWriter,
+    TagBehavior,
+};
+use crate::protocol::{Positions, RoomInfo, ServerEvent};
+use crate::state::RESUME_GRACE;
+use axum::extract::ws::Message;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    net::IpAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use subtle::ConstantTimeEq;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tracing::{debug, trace, warn};
+use uuid::Uuid;
+
+/// Environment variable naming a directory to save every finished match's replay to.
+/// Replays are only kept in memory when it is unset.
+const REPLAY_DIR_VAR: &str = "CTF_REPLAY_DIR";
+
+/// Bytes of replay a room keeps in memory. A match that runs longer stops recording.
+const MAX_REPLAY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Sequenced moves a player may have waiting. Older ones are dropped to make room.
+const MAX_QUEUED_INPUTS: usize = 32;
+
+/// Commands a room can have waiting before senders wait for it to catch up.
+const COMMAND_BUFFER: usize = 256;
+
+/// Messages a connection can fall behind by before it starts missing them.
+const BROADCAST_BUFFER: usize = 256;
+
+pub type Recorder = ReplayWriter<Vec<u8>>;
+
+/// Something to send on a room's connections.
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    Message(Message),
+    /// A `positions` frame, which each connection encodes for itself.
+    Snapshot(Arc<Positions>),
+    /// Tells the connection of session `session_id` to hang up, giving `reason`.
+    Disconnect {
+        session_id: String,
+        reason: &'static str,
+    },
+}
+
+/// A player's moves, applied one per tick in the order the client numbered them.
+#[derive(Debug, Clone)]
+pub struct PlayerInput {
+    /// The move made every tick once the queue runs out.
+    current: Move,
+    queued: VecDeque<(Move, u32)>,
+    /// Sequence number of the newest move applied, echoed back in `positions`.
+    last_seq: Option<u32>,
+}
+
+impl Default for PlayerInput {
+    fn default() -> Self {
+        Self {
+            current: Move::Stay,
+            queued: VecDeque::new(),
+            last_seq: None,
+        }
+    }
+}
+
+impl PlayerInput {
+    /// Takes a move from the client. Numbered moves wait their turn, and ones not newer
+    /// than a move already taken are ignored; moves without a number apply at once.
+    pub fn push(&mut self, new_move: Move, seq: Option<u32>) {
+        let Some(seq) = seq else {
+            self.queued.clear();
+            self.current = new_move;
+            return;
+        };
+        let newest = self.queued.back().map(|&(_, seq)| seq).or(self.last_seq);
+        if newest.is_some_and(|newest| seq <= newest) {
+            return;
+        }
+        if self.queued.len() == MAX_QUEUED_INPUTS {
+            self.queued.pop_front();
+        }
+        self.queued.push_back((new_move, seq));
+    }
+
+    /// The move to make this tick, taking the next queued one if there is one.
+    fn next_move(&mut self) -> Move {
+        if let Some((next, seq)) = self.queued.pop_front() {
+            self.current = next;
+            self.last_seq = Some(seq);
+        }
+        self.current
+    }
+
+    /// Stands the player still, counting every queued move as applied so the client
+    /// stops predicting them.
+    fn stop(&mut self) {
+        if let Some(&(_, seq)) = self.queued.back() {
+            self.last_seq = Some(seq);
+        }
+        self.queued.clear();
+        self.current = Move::Stay;
+    }
+}
+
+/// A player's claim on their slot, kept across reconnects.
+#[derive(Debug, Clone)]
+pub struct PlayerSession {
+    pub player_id: i32,
+    pub session_id: String,
+    pub connected: bool,
+    /// Where the player last connected from.
+    pub address: IpAddr,
+    // Bumped on every disconnect so a stale expiry leaves a resumed session alone
+    disconnects: u32,
+}
+
+impl PlayerSession {
+    /// How many times this player has dropped or been taken over. A connection holds
+    /// on to the count it attached with, see [`Room::suspend`].
+    pub fn disconnects(&self) -> u32 {
+        self.disconnects
+    }
+}
+
+/// What only the room's host may do.
+#[derive(Debug, Clone)]
+pub enum HostAction {
+    Start,
+    /// Removes a player from the room. They may join again.
+    Kick {
+        player_id: i32,
+    },
+    /// Removes a player or spectator for good: a banned player's resume token stops
+    /// working, and with `by_address` their address is refused from then on too.
+    Ban {
+        session_id: String,
+        by_address: bool,
+    },
+    /// Stops new players from joining while `locked`. Players can still resume.
+    Lock {
+        locked: bool,
+    },
+    /// Throws the match away and goes back to the lobby with the same players.
+    Restart,
+    /// Makes a connected player the host instead.
+    Transfer {
+        player_id: i32,
+    },
+}
+
+/// How a room is set up. It can be changed until the match starts.
+#[derive(Debug, Clone)]
+pub struct RoomSettings {
+    pub team_size: usize,
+    pub rules: GameRules,
+    /// `positions` frames sent per second.
+    pub snapshot_rate: u32,
+}
+
+impl RoomSettings {
+    /// A new game on `map` set up this way, or an error if these settings do not make one.
+    pub fn new_game(&self, map: &Map) -> Result<GameState> {
+        if !(1..=MAX_TEAM_SIZE).contains(&self.team_size) {
+            return Err(Error::InvalidRoomConfig(format!(
+                "team_size must be between 1 and {MAX_TEAM_SIZE}"
+            )));
+        }
+        self.rules.validate().map_err(Error::InvalidRoomConfig)?;
+        // Frames are only sent after a tick, so there can be no more of them than ticks
+        if !(1..=self.rules.tick_rate).contains(&self.snapshot_rate) {
+            return Err(Error::InvalidRoomConfig(format!(
+                "snapshot_rate must be between 1 and tick_rate ({})",
+                self.rules.tick_rate
+            )));
+        }
+        if self.rules.tag_behavior == TagBehavior::Jail && !map.has_jails() {
+            return Err(Error::InvalidRoomConfig(format!(
+                "map {} has no jails for jail mode",
+                map.name()
+            )));
+        }
+        Ok(GameState::from_map(map, self.team_size)
+            .map_err(|err| Error::InvalidRoomConfig(err.to_string()))?
+            .with_rules(self.rules.clone()))
+    }
+}
+
+/// A room as `GET /rooms` lists it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomSummary {
+    #[serde(flatten)]
+    pub info: RoomInfo,
+    pub phase: MatchPhase,
+    pub map: String,
+    /// When the room was created, in seconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+/// Everything about a room, as `GET /rooms/{room_key}` shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomDetails {
+    #[serde(flatten)]
+    pub summary: RoomSummary,
+    pub team_size: usize,
+    pub rules: GameRules,
+    pub scores: Vec<usize>,
+}
+
+/// Compares a secret token in constant time, so timing does not give it away.
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.as_bytes().ct_eq(given.as_bytes()).into()
+}
+
+/// Whether a `positions` frame is due after `tick`, spreading `snapshot_rate` frames
+/// as evenly as whole ticks allow over every `tick_rate` ticks.
+pub fn is_snapshot_tick(tick: u64, tick_rate: u32, snapshot_rate: u32) -> bool {
+    let frames_by = |tick: u64| tick * u64::from(snapshot_rate) / u64::from(tick_rate);
+    tick > 0 && frames_by(tick) > frames_by(tick - 1)
+}
+
+/// Starts recording a new room's match in memory.
+fn start_replay(
+    room_key: &str,
+    map: &Map,
+    team_size: usize,
+    rules: &GameRules,
+) -> Option<Recorder> {
+    match ReplayWriter::new(Vec::new(), map, team_size, rules) {
+        Ok(recorder) => Some(recorder),
+        Err(err) => {
+            warn!(room = %room_key, %err, "could not start replay");
+            None
+        }
+    }
+}
+
+/// Saves a finished replay to the replay directory, if one is configured. The file
+/// is written on the blocking pool so the room keeps ticking meanwhile.
+fn save_replay(room_key: &str, replay: &[u8]) {
+    let Some(dir) = std::env::var_os(REPLAY_DIR_VAR) else {
+        return;
+    };
+    let finished_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let path = Path::new(&dir).join(format!("{room_key}-{finished_at}.jsonl"));
+    let (room_key, replay) = (room_key.to_string(), replay.to_vec());
+    tokio::task::spawn_blocking(move || {
+        match fs::create_dir_all(&dir).and_then(|_| fs::write(&path, replay)) {
+            Ok(()) => debug!(room = %room_key, path = %path.display(), "saved replay"),
+            Err(err) => {
+                warn!(room = %room_key, path = %path.display(), %err, "could not save replay")
+            }
+        }
+    });
+}
+
+/// What a [`Room`] asks of its actor. Commands that answer carry a channel for the reply.
+enum Command {
+    Subscribe {
+        reply: oneshot::Sender<broadcast::Receiver<Outgoing>>,
+    },
+    Join {
+        session_id: String,
+        address: IpAddr,
+        reply: oneshot::Sender<Result<Option<(i32, String)>>>,
+    },
+    Resume {
+        token: String,
+        address: IpAddr,
+        reply: oneshot::Sender<Result<Option<PlayerSession>>>,
+    },
+    Suspend {
+        token: String,
+        disconnects: u32,
+        reply: oneshot::Sender<Option<u32>>,
+    },
+    Expire {
+        token: String,
+        disconnects: u32,
+        reply: oneshot::Sender<Option<PlayerSession>>,
+    },
+    AddSpectator {
+        session_id: String,
+        address: IpAddr,
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    RemoveSpectator {
+        session_id: String,
+        reply: oneshot::Sender<usize>,
+    },
+    ClaimHost {
+        session_id: String,
+        token: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    ReleaseHost {
+        session_id: String,
+    },
+    Host {
+        session_id: String,
+        action: HostAction,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Move {
+        player_id: i32,
+        new_move: Move,
+        seq: Option<u32>,
+    },
+    Chat {
+        from: String,
+        content: String,
+    },
+    Announce(ServerEvent),
+    Info {
+        reply: oneshot::Sender<RoomInfo>,
+    },
+    Details {
+        reply: oneshot::Sender<RoomDetails>,
+    },
+    Configure {
+        team_size: Option<usize>,
+        rules: Option<GameRules>,
+        snapshot_rate: Option<u32>,
+        reply: oneshot::Sender<Result<RoomDetails>>,
+    },
+    Replay {
+        reply: oneshot::Sender<Option<Vec<u8>>>,
+    },
+    IdleFor {
+        reply: oneshot::Sender<Option<Duration>>,
+    },
+    Close,
+}
+
+/// A handle to a room's actor. Every method fails with [`Error::RoomNotFound`]
+/// once the room has closed.
+#[derive(Debug)]
+pub struct Room {
+    pub key: String,
+    /// Makes a connection the room's host. Only whoever created the room gets it.
+    pub host_token: String,
+    commands: mpsc::Sender<Command>,
+    task: JoinHandle<()>,
+}
+
+impl Room {
+    /// Starts the actor for a new room playing `game`, which `settings` made on `map`,
+    /// and returns its handle.
+    pub fn spawn(key: String, map: Map, settings: RoomSettings, game: GameState) -> Arc<Room> {
+        let (commands, commands_rx) = mpsc::channel(COMMAND_BUFFER);
+        let (events, _) = broadcast::channel(BROADCAST_BUFFER);
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let actor = RoomActor {
+            replay: start_replay(&key, &map, settings.team_size, game.rules()),
+            host_token: Uuid::new_v4().to_string(),
+            host: None,
+            host_vacant_since: None,
+            locked: false,
+            banned_tokens: HashSet::new(),
+            banned_addresses: HashSet::new(),
+            key,
+            map,
+            settings,
+            created_at,
+            game,
+            inputs: HashMap::new(),
+            spectators: HashMap::new(),
+            sessions: HashMap::new(),
+            events,
+            ticking: false,
+            tick: 0,
+            snapshot: 0,
+            pending_events: Vec::new(),
+            idle_since: Some(Instant::now()),
+        };
+        Arc::new(Room {
+            key: actor.key.clone(),
+            host_token: actor.host_token.clone(),
+            commands,
+            task: tokio::spawn(actor.run(commands_rx)),
+        })
+    }
+
+    async fn send(&self, command: Command) -> Result<()> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| Error::RoomNotFound)
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Result<T> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.send(command(reply)).await?;
+        reply_rx.await.map_err(|_| Error::RoomNotFound)
+    }
+
+    /// Starts receiving everything the room broadcasts from now on.
+    pub async fn subscribe(&self) -> Result<broadcast::Receiver<Outgoing>> {
+        self.request(|reply| Command::Subscribe { reply }).await
+    }
+
+    /// Gives the first free player slot to a new player connecting from `address`,
+    /// returning their player id and resume token, or `None` if the room is full.
+    /// Fails if the room is locked or the address is banned.
+    pub async fn join(&self, session_id: &str, address: IpAddr) -> Result<Option<(i32, String)>> {
+        let session_id = session_id.to_string();
+        self.request(|reply| Command::Join {
+            session_id,
+            address,
+            reply,
+        })
+        .await?
+    }
+
+    /// Reattaches a player to their slot, taking it over from any connection that
+    /// still holds it. Gives `None` if the token is unknown in this room.
+    pub async fn resume(&self, token: &str, address: IpAddr) -> Result<Option<PlayerSession>> {
+        let token = token.to_string();
+        self.request(|reply| Command::Resume {
+            token,
+            address,
+            reply,
+        })
+        .await?
+    }
+
+    /// Holds a disconnected player's slot, freezing them in place until they resume.
+    /// `disconnects` is the count the connection attached with, so one that has been
+    /// taken over leaves the slot alone. Gives the disconnect to pass to
+    /// [`Room::expire`] once the grace period is over.
+    pub async fn suspend(&self, token: &str, disconnects: u32) -> Result<Option<u32>> {
+        let token = token.to_string();
+        self.request(|reply| Command::Suspend {
+            token,
+            disconnects,
+            reply,
+        })
+        .await
+    }
+
+    /// Gives up a slot whose player did not come back after `disconnects`, returning
+    /// the session that was dropped. Does nothing if the player has resumed since.
+    pub async fn expire(&self, token: &str, disconnects: u32) -> Result<Option<PlayerSession>> {
+        let token = token.to_string();
+        self.request(|reply| Command::Expire {
+            token,
+            disconnects,
+            reply,
+        })
+        .await
+    }
+
+    /// Counts a new spectator in the room, returning how many are watching now.
+    /// Fails if the address is banned.
+    pub async fn add_spectator(&self, session_id: &str, address: IpAddr) -> Result<usize> {
+        let session_id = session_id.to_string();
+        self.request(|reply| Command::AddSpectator {
+            session_id,
+            address,
+            reply,
+        })
+        .await?
+    }
+
+    /// Stops counting a spectator who left, returning how many are still watching.
+    pub async fn remove_spectator(&self, session_id: &str) -> Result<usize> {
+        let session_id = session_id.to_string();
+        self.request(|reply| Command::RemoveSpectator { session_id, reply })
+            .await
+    }
+
+    pub fn is_host_token(&self, token: &str) -> bool {
+        tokens_match(&self.host_token, token)
+    }
+
+    /// Makes session `session_id` the host if `token` is the room's host token,
+    /// taking over from any other host.
+    pub async fn claim_host(&self, session_id: &str, token: &str) -> Result<()> {
+        let session_id = session_id.to_string();
+        let token = token.to_string();
+        self.request(|reply| Command::ClaimHost {
+            session_id,
+            token,
+            reply,
+        })
+        .await?
+    }
+
+    /// Tells the room the host's connection has left, if session `session_id` is the
+    /// host. Hosting passes to a connected player, or the room closes if nobody
+    /// takes it over in time.
+    pub async fn release_host(&self, session_id: &str) -> Result<()> {
+        let session_id = session_id.to_string();
+        self.send(Command::ReleaseHost { session_id }).await
+    }
+
+    /// Carries out `action` for session `session_id`, failing unless it is the host.
+    pub async fn host(&self, session_id: &str, action: HostAction) -> Result<()> {
+        let session_id = session_id.to_string();
+        self.request(|reply| Command::Host {
+            session_id,
+            action,
+            reply,
+        })
+        .await?
+    }
+
+    /// Takes a move from the player, numbered with `seq` if the client numbers its moves.
+    pub async fn move_player(
+        &self,
+        player_id: i32,
+        new_move: Move,
+        seq: Option<u32>,
+    ) -> Result<()> {
+        self.send(Command::Move {
+            player_id,
+            new_move,
+            seq,
+        })
+        .await
+    }
+
+    pub async fn chat(&self, from: &str, content: String) -> Result<()> {
+        let from = from.to_string();
+        self.send(Command::Chat { from, content }).await
+    }
+
+    /// Broadcasts `event` to everyone in the room.
+    pub async fn announce(&self, event: ServerEvent) -> Result<()> {
+        self.send(Command::Announce(event)).await
+    }
+
+    pub async fn info(&self) -> Result<RoomInfo> {
+        self.request(|reply| Command::Info { reply }).await
+    }
+
+    pub async fn details(&self) -> Result<RoomDetails> {
+        self.request(|reply| Command::Details { reply }).await
+    }
+
+    /// Changes the settings given and keeps the rest. Fails if the match has started
+    /// or the new settings do not make a game for the players already in the room.
+    pub async fn configure(
+        &self,
+        team_size: Option<usize>,
+        rules: Option<GameRules>,
+        snapshot_rate: Option<u32>,
+    ) -> Result<RoomDetails> {
+        self.request(|reply| Command::Configure {
+            team_size,
+            rules,
+            snapshot_rate,
+            reply,
+        })
+        .await?
+    }
+
+    /// Returns the room's input history so far as a replay file, if it is recording.
+    pub async fn replay(&self) -> Result<Option<Vec<u8>>> {
+        self.request(|reply| Command::Replay { reply }).await
+    }
+
+    /// How long nobody has been connected to the room, or `None` while someone is.
+    pub async fn idle_for(&self) -> Result<Option<Duration>> {
+        self.request(|reply| Command::IdleFor { reply }).await
+    }
+
+    /// Stops the room's actor, which hangs up on everyone connected. Returns false if
+    /// it had already stopped.
+    pub async fn close(&self) -> bool {
+        if self.task.is_finished() {
+            return false;
+        }
+        // An actor that cannot take the command is stuck, so it is stopped outright
+        if self.send(Command::Close).await.is_err() {
+            self.task.abort();
+        }
+        true
+    }
+}
+
+/// Everything in a room, owned by the room's task.
+struct RoomActor {
+    key: String,
+    map: Map,
+    settings: RoomSettings,
+    created_at: u64, // seconds since the Unix epoch
+    game: GameState,
+    inputs: HashMap<i32, PlayerInput>, // player id -> moves of everyone playing
+    replay: Option<Recorder>,          // per-tick input history
+    spectators: HashMap<String, IpAddr>, // session id -> address
+    sessions: HashMap<String, PlayerSession>, // resume token -> player session
+    host_token: String,
+    host: Option<String>, // the host's session id
+    // When the host left with nobody to take over, if they have not been replaced
+    host_vacant_since: Option<Instant>,
+    locked: bool,
+    banned_tokens: HashSet<String>, // resume tokens of banned players
+    // Only filled by bans that ask for it, as one address may be many people
+    banned_addresses: HashSet<IpAddr>,
+    events: broadcast::Sender<Outgoing>,
+    ticking: bool,
+    tick: u64,
+    snapshot: u32,
+    // Events since the last frame, sent along with the next one
+    pending_events: Vec<GameEvent>,
+    // When the last connection left, if nobody is connected
+    idle_since: Option<Instant>,
+}
+
+impl RoomActor {
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>) {
+        let mut ticker = time::interval(self.game.rules().tick_interval());
+        let mut was_live = false;
+        loop {
+            // The tick rate can be changed in the lobby
+            if ticker.period() != self.game.rules().tick_interval() {
+                ticker = time::interval(self.game.rules().tick_interval());
+            }
+            // The game only runs while someone is connected to see it
+            let live = self.ticking && self.idle_since.is_none();
+            if live && !was_live {
+                // Otherwise the ticks missed while paused would all run at once
+                ticker.reset();
+            } else if was_live && !live && self.ticking {
+                debug!(room = %self.key, "nobody connected, pausing tick loop");
+            }
+            was_live = live;
+            let host_deadline = self.host_vacant_since.map(|since| since + RESUME_GRACE);
+
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(Command::Close) | None => break,
+                    Some(command) => self.handle(command),
+                },
+                _ = ticker.tick(), if live => self.step(),
+                _ = time::sleep_until(host_deadline.unwrap_or_else(Instant::now)), if host_deadline.is_some() => {
+                    if !self.replace_host() {
+                        debug!(room = %self.key, "host did not come back, closing room");
+                        break;
+                    }
+                }
+            }
+            self.update_idle();
+        }
+        // Dropping the broadcast sender tells every connection the room is gone
+        debug!(room = %self.key, "room closed");
+    }
+
+    fn handle(&mut self, command: Command) {
+        // A requester that gave up waiting no longer needs the reply
+        match command {
+            Command::Subscribe { reply } => {
+                let _ = reply.send(self.events.subscribe());
+            }
+            Command::Join {
+                session_id,
+                address,
+                reply,
+            } => {
+                let _ = reply.send(self.join(session_id, address));
+            }
+            Command::Resume {
+                token,
+                address,
+                reply,
+            } => {
+                let _ = reply.send(self.resume(&token, address));
+            }
+            Command::Suspend {
+                token,
+                disconnects,
+                reply,
+            } => {
+                let _ = reply.send(self.suspend(&token, disconnects));
+            }
+            Command::Expire {
+                token,
+                disconnects,
+                reply,
+            } => {
+                let _ = reply.send(self.expire(&token, disconnects));
+            }
+            Command::AddSpectator {
+                session_id,
+                address,
+                reply,
+            } => {
+                let admitted = self.admit(address).map(|()| {
+                    self.spectators.insert(session_id, address);
+                    self.spectators.len()
+                });
+                let _ = reply.send(admitted);
+            }
+            Command::RemoveSpectator { session_id, reply } => {
+                self.spectators.remove(&session_id);
+                let _ = reply.send(self.spectators.len());
+            }
+            Command::ClaimHost {
+                session_id,
+                token,
+                reply,
+            } => {
+                let _ = reply.send(self.claim_host(session_id, &token));
+            }
+            Command::ReleaseHost { session_id } => {
+                if self.host.as_ref() == Some(&session_id) {
+                    self.host_left();
+                }
+            }
+            Command::Host {
+                session_id,
+                action,
+                reply,
+            } => {
+                let _ = reply.send(self.host_action(session_id, action));
+            }
+            Command::Move {
+                player_id,
+                new_move,
+                seq,
+            } => {
+                // Moves from a player whose slot has been given up are dropped
+                if let Some(input) = self.inputs.get_mut(&player_id) {
+                    input.push(new_move, seq);
+                }
+            }
+            Command::Chat { from, content } => {
+                self.broadcast(&ServerEvent::Chat { from, content });
+            }
+            Command::Announce(event) => self.broadcast(&event),
+            Command::Info { reply } => {
+                let _ = reply.send(self.info());
+            }
+            Command::Details { reply } => {
+                let _ = reply.send(self.details());
+            }
+            Command::Configure {
+                team_size,
+                rules,
+                snapshot_rate,
+                reply,
+            } => {
+                let settings = RoomSettings {
+                    team_size: team_size.unwrap_or(self.settings.team_size),
+                    rules: rules.unwrap_or_else(|| self.settings.rules.clone()),
+                    snapshot_rate: snapshot_rate.unwrap_or(self.settings.snapshot_rate),
+                };
+                let _ = reply.send(self.configure(settings));
+            }
+            Command::Replay { reply } => {
+                let replay = self
+                    .replay
+                    .as_ref()
+                    .map(|recorder| recorder.get_ref().clone());
+                let _ = reply.send(replay);
+            }
+            Command::IdleFor { reply } => {
+                self.update_idle();
+                let _ = reply.send(self.idle_since.map(|since| since.elapsed()));
+            }
+            Command::Close => {}
+        }
+    }
+
+    /// Refuses banned addresses.
+    fn admit(&self, address: IpAddr) -> Result<()> {
+        if self.banned_addresses.contains(&address) {
+            return Err(Error::Forbidden(
+                "you are banned from this room".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn join(&mut self, session_id: String, address: IpAddr) -> Result<Option<(i32, String)>> {
+        self.admit(address)?;
+        if self.locked {
+            return Err(Error::Forbidden("the room is locked".to_string()));
+        }
+        // Find first available slot in the room's game
+        let player_count = self.game.player_count() as i32;
+        let Some(player_id) = (0..player_count).find(|id| !self.inputs.contains_key(id)) else {
+            return Ok(None);
+        };
+        self.inputs.insert(player_id, PlayerInput::default());
+
+        let token = Uuid::new_v4().to_string();
+        self.sessions.insert(
+            token.clone(),
+            PlayerSession {
+                player_id,
+                session_id,
+                connected: true,
+                address,
+                disconnects: 0,
+            },
+        );
+        Ok(Some((player_id, token)))
+    }
+
+    fn resume(&mut self, token: &str, address: IpAddr) -> Result<Option<PlayerSession>> {
+        if self.banned_tokens.contains(token) {
+            return Err(Error::Forbidden(
+                "you are banned from this room".to_string(),
+            ));
+        }
+        self.admit(address)?;
+        let Some(session) = self.sessions.get_mut(token) else {
+            return Ok(None);
+        };
+        // The old socket may not have noticed it is gone yet; close it for them
+        if session.connected {
+            session.disconnects += 1;
+            let _ = self.events.send(Outgoing::Disconnect {
+                session_id: session.session_id.clone(),
+                reason: "resumed elsewhere",
+            });
+        }
+        session.connected = true;
+        session.address = address;
+        // A reloaded client numbers its moves from the start again
+        self.inputs
+            .insert(session.player_id, PlayerInput::default());
+        Ok(Some(session.clone()))
+    }
+
+    fn suspend(&mut self, token: &str, disconnects: u32) -> Option<u32> {
+        let session = self
+            .sessions
+            .get_mut(token)
+            .filter(|session| session.connected && session.disconnects == disconnects)?;
+        session.connected = false;
+        session.disconnects += 1;
+        if let Some(input) = self.inputs.get_mut(&session.player_id) {
+            input.stop();
+        }
+        let disconnects = session.disconnects;
+        // A host who cannot play cannot run the room either
+        if self.host.as_ref() == Some(&session.session_id) {
+            self.host_left();
+        }
+        Some(disconnects)
+    }
+
+    fn expire(&mut self, token: &str, disconnects: u32) -> Option<PlayerSession> {
+        let session = self.sessions.get(token)?;
+        if session.connected || session.disconnects != disconnects {
+            return None;
+        }
+        self.remove_player(token)
+    }
+
+    fn remove_player(&mut self, token: &str) -> Option<PlayerSession> {
+        let session = self.sessions.remove(token)?;
+        self.inputs.remove(&session.player_id);
+        Some(session)
+    }
+
+    fn claim_host(&mut self, session_id: String, token: &str) -> Result<()> {
+        if !tokens_match(&self.host_token, token) {
+            return Err(Error::Forbidden("the host token is invalid".to_string()));
+        }
+        self.set_host(session_id, None);
+        Ok(())
+    }
+
+    fn set_host(&mut self, session_id: String, player_id: Option<i32>) {
+        self.host_vacant_since = None;
+        if self.host.as_ref() == Some(&session_id) {
+            return;
+        }
+        debug!(room = %self.key, session = %session_id, "new host");
+        self.host = Some(session_id.clone());
+        self.broadcast(&ServerEvent::HostChanged {
+            session_id,
+            player_id,
+        });
+    }
+
+    /// Hands hosting to a connected player, or holds it for the host to come back
+    /// if nobody is playing.
+    fn host_left(&mut self) {
+        let Some(session_id) = self.host.take() else {
+            return;
+        };
+        self.broadcast(&ServerEvent::HostLeft { session_id });
+        if !self.replace_host() {
+            self.host_vacant_since = Some(Instant::now());
+        }
+    }
+
+    /// Makes the connected player with the lowest player id the host. Returns false if
+    /// nobody is playing.
+    fn replace_host(&mut self) -> bool {
+        let next = self
+            .sessions
+            .values()
+            .filter(|session| session.connected)
+            .min_by_key(|session| session.player_id)
+            .map(|session| (session.session_id.clone(), session.player_id));
+        let Some((session_id, player_id)) = next else {
+            return false;
+        };
+        self.set_host(session_id, Some(player_id));
+        true
+    }
+
+    fn host_action(&mut self, session_id: String, action: HostAction) -> Result<()> {
+        if self.host.as_ref() != Some(&session_id) {
+            return Err(Error::Forbidden("only the host can do that".to_string()));
+        }
+        match action {
+            HostAction::Start => {
+                self.start(session_id);
+            }
+            HostAction::Kick { player_id } => {
+                let token = self
+                    .session_token(|session| session.player_id == player_id)
+                    .ok_or(Error::PlayerNotFound)?;
+                self.kick(&token, false);
+            }
+            HostAction::Ban {
+                session_id: banned,
+                by_address,
+            } => {
+                let address = if let Some(token) =
+                    self.session_token(|session| session.session_id == banned)
+                {
+                    let address = self.kick(&token, true);
+                    self.banned_tokens.insert(token);
+                    address
+                } else if let Some(address) = self.spectators.remove(&banned) {
+                    self.disconnect(banned, None, true);
+                    Some(address)
+                } else {
+                    return Err(Error::PlayerNotFound);
+                };
+                if by_address {
+                    self.banned_addresses.extend(address);
+                }
+            }
+            HostAction::Lock { locked } => {
+                self.locked = locked;
+                self.broadcast(&ServerEvent::RoomLocked { locked });
+            }
+            HostAction::Restart => self.restart(session_id)?,
+            HostAction::Transfer { player_id } => {
+                let next = self
+                    .sessions
+                    .values()
+                    .find(|session| session.player_id == player_id && session.connected)
+                    .ok_or(Error::PlayerNotFound)?;
+                self.set_host(next.session_id.clone(), Some(player_id));
+            }
+        }
+        Ok(())
+    }
+
+    /// The resume token of the first player session matching `filter`.
+    fn session_token(&self, filter: impl Fn(&PlayerSession) -> bool) -> Option<String> {
+        self.sessions
+            .iter()
+            .find(|(_, session)| filter(session))
+            .map(|(token, _)| token.clone())
+    }
+
+    /// Takes a player's slot away and hangs up on them, returning where they
+    /// connected from.
+    fn kick(&mut self, token: &str, banned: bool) -> Option<IpAddr> {
+        let session = self.remove_player(token)?;
+        self.disconnect(session.session_id.clone(), Some(session.player_id), banned);
+        // Hosts can kick themselves out of their player slot too
+        if self.host.as_ref() == Some(&session.session_id) {
+            self.host_left();
+        }
+        Some(session.address)
+    }
+
+    fn disconnect(&mut self, session_id: String, player_id: Option<i32>, banned: bool) {
+        debug!(room = %self.key, session = %session_id, banned, "removing session");
+        let _ = self.events.send(Outgoing::Disconnect {
+            session_id: session_id.clone(),
+            reason: if banned { "banned" } else { "kicked" },
+        });
+        self.broadcast(&ServerEvent::UserKicked {
+            session_id,
+            player_id,
+            banned,
+        });
+    }
+
+    /// Throws the match away and goes back to the lobby, keeping everyone's slot.
+    fn restart(&mut self, restarted_by: String) -> Result<()> {
+        self.game = self.settings.new_game(&self.map)?;
+        self.replay = start_replay(
+            &self.key,
+            &self.map,
+            self.settings.team_size,
+            &self.settings.rules,
+        );
+        for input in self.inputs.values_mut() {
+            input.stop();
+        }
+        self.ticking = false;
+        self.tick = 0;
+        self.snapshot = 0;
+        self.pending_events.clear();
+        self.broadcast(&ServerEvent::MatchRestarted { restarted_by });
+        Ok(())
+    }
+
+    /// Notes when the room emptied. Connections leave by dropping their receiver, so
+    /// this is checked after everything the actor does.
+    fn update_idle(&mut self) {
+        if self.events.receiver_count() > 0 {
+            self.idle_since = None;
+        } else if self.idle_since.is_none() {
+            self.idle_since = Some(Instant::now());
+        }
+    }
+
+    fn info(&self) -> RoomInfo {
+        RoomInfo {
+            room_key: self.key.clone(),
+            players: self.inputs.len(),
+            max_players: self.game.player_count(),
+            spectators: self.spectators.len(),
+            locked: self.locked,
+            host: self.host.clone(),
+            tick_rate: self.game.rules().tick_rate,
+            snapshot_rate: self.settings.snapshot_rate,
+        }
+    }
+
+    fn details(&self) -> RoomDetails {
+        RoomDetails {
+            summary: RoomSummary {
+                info: self.info(),
+                phase: self.game.phase(),
+                map: self.map.name().to_string(),
+                created_at: self.created_at,
+            },
+            team_size: self.settings.team_size,
+            rules: self.settings.rules.clone(),
+            scores: self.game.get_scores().to_vec(),
+        }
+    }
+
+    /// Sets the room up again with `settings` and tells everyone connected.
+    fn configure(&mut self, settings: RoomSettings) -> Result<RoomDetails> {
+        if self.game.phase() != MatchPhase::Lobby {
+            return Err(Error::MatchStarted);
+        }
+        let game = settings.new_game(&self.map)?;
+        if let Some(player_id) = self
+            .inputs
+            .keys()
+            .find(|&&player_id| player_id as usize >= game.player_count())
+        {
+            return Err(Error::InvalidRoomConfig(format!(
+                "player {player_id} would have no slot in a room for {} players",
+                game.player_count()
+            )));
+        }
+        // Nothing has been recorded in the lobby, so the replay starts over
+        self.replay = start_replay(&self.key, &self.map, settings.team_size, &settings.rules);
+        self.game = game;
+        self.settings = settings;
+        self.broadcast(&ServerEvent::RoomUpdated {
+            room_info: self.info(),
+            rules: self.settings.rules.clone(),
+        });
+        Ok(self.details())
+    }
+
+    /// Broadcast a message to everyone connected. Nobody listening is not an error.
+    fn broadcast(&self, event: &ServerEvent) {
+        let _ = self
+            .events
+            .send(Outgoing::Message(Message::text(event.to_json())));
+    }
+
+    /// Writes to the room's replay, if it has one, giving up on the replay if writing
+    /// fails or it grows past [`MAX_REPLAY_BYTES`].
+    fn record(&mut self, write: impl FnOnce(&mut Recorder) -> io::Result<()>) {
+        if let Some(recorder) = &mut self.replay
+            && let Err(err) = write(recorder).and_then(|()| {
+                if recorder.get_ref().len() > MAX_REPLAY_BYTES {
+                    return Err(io::Error::other("replay is too long to keep"));
+                }
+                Ok(())
+            })
+        {
+            warn!(room = %self.key, %err, "stopped recording replay");
+            self.replay = None;
+        }
+    }
+
+    fn start(&mut self, started_by: String) -> bool {
+        let events = self.game.start();
+        if events.is_empty() {
+            return false;
+        }
+        self.record(|recorder| recorder.record_start());
+        self.broadcast(&ServerEvent::GameStarted { started_by });
+        self.broadcast(&ServerEvent::GameEvents { events });
+        self.ticking = true;
+        self.tick = 0;
+        self.snapshot = 0;
+        true
+    }
+
+    /// Runs one tick of the game, broadcasting a frame if one is due.
+    fn step(&mut self) {
+        self.tick += 1;
+
+        // A player's id is the index of their piece in the game
+        let player_count = self.game.player_count();
+        let mut moves = vec![Move::Stay; player_count];
+        let mut input_seqs = vec![None; player_count];
+        for (&player_id, input) in &mut self.inputs {
+            let index = player_id as usize;
+            if index < player_count {
+                moves[index] = input.next_move();
+                input_seqs[index] = input.last_seq;
+            }
+        }
+
+        self.record(|recorder| recorder.record_step(&moves));
+        let events = self.game.step(&moves);
+
+        for event in &events {
+            match event {
+                GameEvent::PlayerMoved { .. } => {
+                    trace!(room = %self.key, ?event, "game event")
+                }
+                _ => debug!(room = %self.key, ?event, "game event"),
+            }
+        }
+
+        // Someone scored, so everyone starts the next round standing still
+        if events
+            .iter()
+            .any(|event| matches!(event, GameEvent::Scored { .. }))
+        {
+            for input in self.inputs.values_mut() {
+                input.stop();
+            }
+            debug!(room = %self.key, "all player moves reset to Stay after score");
+        }
+        // Players caught in enemy territory stop moving after respawning
+        for event in &events {
+            if let GameEvent::PlayerTagged { player, .. } = event
+                && let Some(input) = self.inputs.get_mut(&(*player as i32))
+            {
+                input.stop();
+                debug!(room = %self.key, "reset move to Stay for player {}", player);
+            }
+        }
+
+        // The match is decided, so there is nothing left to simulate
+        let match_over = events.iter().find_map(|event| match event {
+            GameEvent::MatchOver {
+                winner,
+                scores,
+                overtime,
+            } => Some((*winner, scores.clone(), *overtime)),
+            _ => None,
+        });
+
+        // The final frame always goes out, so everyone sees how the match ended
+        self.pending_events.extend(events);
+        if match_over.is_some()
+            || is_snapshot_tick(
+                self.tick,
+                self.game.rules().tick_rate,
+                self.settings.snapshot_rate,
+            )
+        {
+            self.snapshot += 1;
+            let positions =
+                Positions::from_game(&self.game, self.snapshot).with_input_seqs(input_seqs);
+            let _ = self.events.send(Outgoing::Snapshot(Arc::new(positions)));
+            if !self.pending_events.is_empty() {
+                let events = std::mem::take(&mut self.pending_events);
+                self.broadcast(&ServerEvent::GameEvents { events });
+            }
+        }
+
+        let Some((winner, scores, overtime)) = match_over else {
+            return;
+        };
+        self.broadcast(&ServerEvent::MatchOver {
+            winner,
+            winner_name: Some(self.game.team_name(winner).to_string()),
+            scores,
+            overtime,
+        });
+        if let Some(recorder) = &mut self.replay {
+            match recorder.finish(&self.game) {
+                Ok(()) => save_replay(&self.key, recorder.get_ref()),
+                Err(err) => warn!(room = %self.key, %err, "could not finish replay"),
+            }
+        }
+        self.ticking = false;
+        debug!(room = %self.key, "match over, tick loop stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot_ticks(tick_rate: u32, snapshot_rate: u32) -> Vec<u64> {
+        (1..=u64::from(tick_rate))
+            .filter(|&tick| is_snapshot_tick(tick, tick_rate, snapshot_rate))
+            .collect()
+    }
+
+    #[test]
+    fn spreads_snapshots_evenly_over_ticks() {
+        assert_eq!(
+            snapshot_ticks(30, 15),
+            (2..=30).step_by(2).collect::<Vec<_>>()
+        );
+        assert_eq!(snapshot_ticks(30, 30), (1..=30).collect::<Vec<_>>());
+        assert_eq!(snapshot_ticks(30, 20).len(), 20);
+        assert_eq!(snapshot_ticks(30, 1), vec![30]);
+        assert!(!is_snapshot_tick(0, 30, 30));
+    }
+
+    #[test]
+    fn applies_numbered_moves_one_per_tick_in_order() {
+        let mut input = PlayerInput::default();
+        input.push(Move::Right, Some(1));
+        input.push(Move::Up, Some(3));
+        // Arrived late, after a newer move
+        input.push(Move::Left, Some(2));
+
+        assert_eq!((input.next_move(), input.last_seq), (Move::Right, Some(1)));
+        assert_eq!((input.next_move(), input.last_seq), (Move::Up, Some(3)));
+        // The last move carries on once the queue runs out
+        assert_eq!((input.next_move(), input.last_seq), (Move::Up, Some(3)));
+
+        input.push(Move::Down, Some(4));
+        input.push(Move::Left, Some(5));
+        input.stop();
+        assert_eq!((input.next_move(), input.last_seq), (Move::Stay, Some(5)));
+
+        input.push(Move::Down, None);
+        assert_eq!((input.next_move(), input.last_seq), (Move::Down, Some(5)));
+    }
+}
@@ -1,5 +1,6 @@
 use axum::{
     Json,
+    extract::rejection::JsonRejection,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
@@ -10,18 +11,38 @@ use std::{error::Error as StdError, fmt};
 #[derive(Debug)]
 pub enum Error {
     RoomNotFound,
+    InvalidRoomConfig(String),
+    /// The room's match has started, so it can no longer be set up.
+    MatchStarted,
+    PlayerNotFound,
+    /// The room exists but is not recording its match.
+    NoReplay,
+    /// The connection may not do what it asked, for the reason given.
+    Forbidden(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::RoomNotFound => write!(f, "room not found"),
+            Error::InvalidRoomConfig(reason) => write!(f, "invalid room config: {reason}"),
+            Error::MatchStarted => write!(f, "match already started"),
+            Error::PlayerNotFound => write!(f, "player not found"),
+            Error::NoReplay => write!(f, "room has no replay"),
+            Error::Forbidden(reason) => write!(f, "forbidden: {reason}"),
         }
     }
 }
 
 impl StdError for Error {}
 
+/// A request body that is not valid room settings.
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::InvalidRoomConfig(rejection.body_text())
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
@@ -32,6 +53,41 @@ impl IntoResponse for Error {
                 }));
                 (StatusCode::NOT_FOUND, body).into_response()
             }
+            Error::InvalidRoomConfig(reason) => {
+                let body = Json(json!({
+                    "error": "invalid_room_config",
+                    "message": reason
+                }));
+                (StatusCode::BAD_REQUEST, body).into_response()
+            }
+            Error::MatchStarted => {
+                let body = Json(json!({
+                    "error": "match_started",
+                    "message": "the room's match has already started"
+                }));
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            Error::PlayerNotFound => {
+                let body = Json(json!({
+                    "error": "player_not_found",
+                    "message": "the requested player is not in the room"
+                }));
+                (StatusCode::NOT_FOUND, body).into_response()
+            }
+            Error::NoReplay => {
+                let body = Json(json!({
+                    "error": "no_replay",
+                    "message": "the room is not recording its match"
+                }));
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            Error::Forbidden(reason) => {
+                let body = Json(json!({
+                    "error": "forbidden",
+                    "message": reason
+                }));
+                (StatusCode::FORBIDDEN, body).into_response()
+            }
         }
     }
 }
@@ -0,0 +1,70 @@
+use super::GameState;
+use itertools::Itertools;
+
+/// A player caught in enemy territory by an opponent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+    pub player: usize,
+    pub by: usize,
+}
+
+impl GameState {
+    /// Finds every player tagged at the current positions. Players out of play
+    /// are ignored and protected players cannot be tagged.
+    ///
+    /// All overlapping pairs of opponents are checked against the same snapshot of
+    /// positions, so the outcome does not depend on the order tags are applied in.
+    /// Each tagged player appears once, credited to the lowest-index opponent that
+    /// caught them, and the list is sorted by player index.
+    pub(super) fn find_tags(&self) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = Vec::new();
+
+        for pair in (0..self.player_count()).combinations(2) {
+            let player_index_0 = pair[0];
+            let player_index_1 = pair[1];
+
+            if self.get_player_team(player_index_0) == self.get_player_team(player_index_1) {
+                continue;
+            }
+
+            if !self.is_in_play(player_index_0) || !self.is_in_play(player_index_1) {
+                continue;
+            }
+
+            if !self.players_overlap(player_index_0, player_index_1) {
+                continue;
+            }
+
+            for (player, by) in [
+                (player_index_0, player_index_1),
+                (player_index_1, player_index_0),
+            ] {
+                let left = self.player_x[player];
+                if !self.get_is_player_on_home_side(player, left) && !self.is_protected(player) {
+                    tags.push(Tag { player, by });
+                }
+            }
+        }
+
+        // Pairs are visited in index order, so the first tag per player has the lowest tagger
+        tags.sort_by_key(|tag| (tag.player, tag.by));
+        tags.dedup_by_key(|tag| tag.player);
+        tags
+    }
+
+    fn players_overlap(&self, player_index_0: usize, player_index_1: usize) -> bool {
+        let player_0_left = self.player_x[player_index_0];
+        let player_0_top = self.player_y[player_index_0];
+        let player_0_right = player_0_left + self.rules.player_size;
+        let player_0_bottom = player_0_top + self.rules.player_size;
+
+        let player_1_left = self.player_x[player_index_1];
+        let player_1_top = self.player_y[player_index_1];
+        let player_1_right = player_1_left + self.rules.player_size;
+        let player_1_bottom = player_1_top + self.rules.player_size;
+
+        let is_collide_x = player_0_left < player_1_right && player_1_left < player_0_right;
+        let is_collide_y = player_0_top < player_1_bottom && player_1_top < player_0_bottom;
+        is_collide_x && is_collide_y
+    }
+}
@@ -0,0 +1,63 @@
+use super::MatchPhase;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Something that happened during a [`GameState::step`](super::GameState::step),
+/// in the order it happened. Player and team values are indices into the game.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    PlayerMoved {
+        player: usize,
+        x: f32,
+        y: f32,
+    },
+    PlayerTagged {
+        player: usize,
+        by: usize,
+    },
+    /// A tagged player was locked in the jail of `jail_team`.
+    PlayerJailed {
+        player: usize,
+        jail_team: usize,
+    },
+    /// A jailed player was freed by their teammate `by`.
+    PlayerRescued {
+        player: usize,
+        by: usize,
+    },
+    /// A tagged player's respawn timer ran out and they are back in play.
+    PlayerRespawned {
+        player: usize,
+    },
+    FlagPicked {
+        team: usize,
+        player: usize,
+    },
+    FlagDropped {
+        team: usize,
+        player: usize,
+        x: f32,
+        y: f32,
+    },
+    /// `by` is the teammate who touched the flag, or `None` if it timed out.
+    FlagReturned {
+        team: usize,
+        by: Option<usize>,
+    },
+    Scored {
+        team: usize,
+        player: usize,
+    },
+    RoundReset,
+    PhaseChanged {
+        phase: MatchPhase,
+    },
+    /// The time limit ran out on a tie, so the next score wins.
+    OvertimeStarted,
+    MatchOver {
+        winner: usize,
+        scores: Vec<usize>,
+        overtime: bool,
+    },
+}
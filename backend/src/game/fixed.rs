@@ -0,0 +1,151 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+/// A length in thousandths of a tile, or a duration in thousandths of a second.
+///
+/// The simulation only ever adds, subtracts and compares these, so it gives the
+/// same results on every platform, unlike floats. Conversions to and from
+/// floats happen only at the edges: reading rules and reporting positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    /// Sub-tile units per tile.
+    pub const SCALE: i32 = 1000;
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(Self::SCALE);
+
+    /// A length of `tiles` whole tiles.
+    pub const fn from_tiles(tiles: i32) -> Self {
+        Fixed(tiles * Self::SCALE)
+    }
+
+    /// A duration of `secs` whole seconds.
+    pub const fn from_secs(secs: i32) -> Self {
+        Fixed(secs * Self::SCALE)
+    }
+
+    /// A length of `raw` thousandths of a tile.
+    pub const fn from_raw(raw: i32) -> Self {
+        Fixed(raw)
+    }
+
+    /// Rounds `tiles` to the nearest thousandth of a tile.
+    /// Returns `None` for values that are not finite or too large to represent.
+    pub fn from_f64(tiles: f64) -> Option<Self> {
+        let raw = (tiles * f64::from(Self::SCALE)).round();
+        (raw.is_finite() && raw >= f64::from(i32::MIN) && raw <= f64::from(i32::MAX))
+            .then_some(Fixed(raw as i32))
+    }
+
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    pub fn to_f64(self) -> f64 {
+        f64::from(self.0) / f64::from(Self::SCALE)
+    }
+
+    /// The index of the tile this length falls in.
+    pub fn floor_tiles(self) -> i32 {
+        self.0.div_euclid(Self::SCALE)
+    }
+
+    /// Half the length, rounded towards negative infinity.
+    pub fn half(self) -> Self {
+        Fixed(self.0.div_euclid(2))
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, factor: i32) -> Fixed {
+        Fixed(self.0 * factor)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+/// Written as a plain number of tiles or seconds, e.g. `0.25`.
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tiles = f64::deserialize(deserializer)?;
+        Fixed::from_f64(tiles)
+            .ok_or_else(|| serde::de::Error::custom(format!("{tiles} tiles is out of range")))
+    }
+}
+
+impl JsonSchema for Fixed {
+    fn schema_name() -> Cow<'static, str> {
+        "Fixed".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "number",
+            "description": "A number of tiles or seconds, rounded to the nearest thousandth.",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_tiles() {
+        let quarter = Fixed::from_f64(0.25).unwrap();
+        assert_eq!(quarter.raw(), 250);
+        assert_eq!(serde_json::to_string(&quarter).unwrap(), "0.25");
+        assert_eq!(serde_json::from_str::<Fixed>("1.2").unwrap().raw(), 1200);
+        assert_eq!(Fixed::from_f64(f64::NAN), None);
+
+        assert_eq!(Fixed::from_raw(-1).floor_tiles(), -1);
+        assert_eq!(Fixed::from_raw(27_999).floor_tiles(), 27);
+        assert_eq!(Fixed::ONE.half(), Fixed::from_raw(500));
+    }
+}
@@ -0,0 +1,151 @@
+use super::{Fixed, GameEvent, GameState};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// A flag lying on the ground where its carrier was tagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct DroppedFlag {
+    pub(super) x: Fixed,
+    pub(super) y: Fixed,
+    pub(super) ticks_until_return: u32,
+}
+
+/// Where a team's flag is, as sent to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub struct FlagStatus {
+    pub team: usize,
+    pub x: f32,
+    pub y: f32,
+    #[serde(flatten)]
+    pub state: FlagState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum FlagState {
+    AtBase,
+    Carried { by: usize },
+    Dropped { ticks_until_return: u32 },
+}
+
+impl GameState {
+    /// Every team's flag, in team order.
+    pub fn flags(&self) -> Vec<FlagStatus> {
+        (0..self.team_count())
+            .map(|team| {
+                let (x, y) = self.flag_position(team);
+                let state = match (self.flag_captors[team], self.dropped_flags[team]) {
+                    (Some(by), _) => FlagState::Carried { by },
+                    (None, Some(dropped)) => FlagState::Dropped {
+                        ticks_until_return: dropped.ticks_until_return,
+                    },
+                    (None, None) => FlagState::AtBase,
+                };
+                FlagStatus {
+                    team,
+                    x: x.to_f32(),
+                    y: y.to_f32(),
+                    state,
+                }
+            })
+            .collect()
+    }
+
+    fn flag_position(&self, team: usize) -> (Fixed, Fixed) {
+        match (self.flag_captors[team], self.dropped_flags[team]) {
+            (Some(player), _) => (self.player_x[player], self.player_y[player]),
+            (None, Some(dropped)) => (dropped.x, dropped.y),
+            (None, None) => (
+                Fixed::from_tiles(self.flag_spawn_x[team] as i32),
+                Fixed::from_tiles(self.flag_spawn_y[team] as i32),
+            ),
+        }
+    }
+
+    /// Drops every flag `player_index` carries at their current position.
+    pub(super) fn drop_flags(&mut self, player_index: usize, events: &mut Vec<GameEvent>) {
+        let (x, y) = (self.player_x[player_index], self.player_y[player_index]);
+        for team in 0..self.team_count() {
+            if self.flag_captors[team] == Some(player_index) {
+                self.flag_captors[team] = None;
+                self.dropped_flags[team] = Some(DroppedFlag {
+                    x,
+                    y,
+                    ticks_until_return: self.rules.ticks(self.rules.flag_return_secs),
+                });
+                events.push(GameEvent::FlagDropped {
+                    team,
+                    player: player_index,
+                    x: x.to_f32(),
+                    y: y.to_f32(),
+                });
+            }
+        }
+    }
+
+    /// Lets players pick up enemy flags and return their own dropped flag, then
+    /// counts down flags nobody has touched.
+    pub(super) fn update_flags(&mut self, events: &mut Vec<GameEvent>) {
+        for team in 0..self.team_count() {
+            // Skip if flag is already captured
+            if self.flag_captors[team].is_some() {
+                continue;
+            }
+
+            let (flag_x, flag_y) = self.flag_position(team);
+            let is_dropped = self.dropped_flags[team].is_some();
+
+            // Lowest player index wins when several players reach the flag together
+            let toucher = (0..self.player_count()).find(|&player_index| {
+                let is_enemy = self.get_player_team(player_index) != team;
+                (is_enemy || is_dropped)
+                    && self.is_in_play(player_index)
+                    && self.is_touching_flag(player_index, flag_x, flag_y)
+            });
+
+            match toucher {
+                Some(player_index) if self.get_player_team(player_index) != team => {
+                    self.flag_captors[team] = Some(player_index);
+                    self.dropped_flags[team] = None;
+                    events.push(GameEvent::FlagPicked {
+                        team,
+                        player: player_index,
+                    });
+                }
+                Some(player_index) => {
+                    self.dropped_flags[team] = None;
+                    events.push(GameEvent::FlagReturned {
+                        team,
+                        by: Some(player_index),
+                    });
+                }
+                None => {}
+            }
+        }
+
+        for team in 0..self.team_count() {
+            if let Some(dropped) = &mut self.dropped_flags[team] {
+                dropped.ticks_until_return = dropped.ticks_until_return.saturating_sub(1);
+                if dropped.ticks_until_return == 0 {
+                    self.dropped_flags[team] = None;
+                    events.push(GameEvent::FlagReturned { team, by: None });
+                }
+            }
+        }
+    }
+
+    fn is_touching_flag(&self, player_index: usize, flag_x: Fixed, flag_y: Fixed) -> bool {
+        // Calculate distance between player center and flag center
+        let player_center_x = self.player_x[player_index] + self.rules.player_size.half();
+        let player_center_y = self.player_y[player_index] + self.rules.player_size.half();
+        let flag_center_x = flag_x + self.rules.flag_size.half();
+        let flag_center_y = flag_y + self.rules.flag_size.half();
+
+        // Compare squared distances to stay in integers
+        let dx = i64::from((player_center_x - flag_center_x).raw());
+        let dy = i64::from((player_center_y - flag_center_y).raw());
+        let capture_distance = i64::from(self.rules.capture_distance.raw());
+
+        dx * dx + dy * dy <= capture_distance * capture_distance
+    }
+}
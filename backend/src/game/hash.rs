@@ -0,0 +1,58 @@
+use super::GameState;
+use std::hash::{Hash, Hasher};
+
+/// 64-bit FNV-1a that writes every integer as little-endian `u64`-or-wider bytes,
+/// so the same state hashes the same on every platform and pointer width.
+struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+impl GameState {
+    /// A fingerprint of the whole simulation state.
+    ///
+    /// The simulation is deterministic: the same starting state and the same
+    /// moves always give the same hash, on any platform. Comparing hashes is a
+    /// cheap way to check that two simulations, e.g. a replay and the original
+    /// game, have not diverged.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StableHasher(StableHasher::OFFSET_BASIS);
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
@@ -0,0 +1,109 @@
+use super::{Fixed, GameEvent, GameState};
+
+impl GameState {
+    /// Whether `player_index` is locked in an enemy jail.
+    pub fn is_jailed(&self, player_index: usize) -> bool {
+        self.jailed[player_index]
+    }
+
+    /// Whether each player is jailed, in player order.
+    pub fn get_jailed(&self) -> &[bool] {
+        &self.jailed
+    }
+
+    /// Each team's jail cell, in team order. Empty if the map has no jails.
+    pub fn jails(&self) -> Vec<(usize, usize)> {
+        self.jail_x
+            .iter()
+            .copied()
+            .zip(self.jail_y.iter().copied())
+            .collect()
+    }
+
+    /// Locks `player_index` in the jail of the team that tagged them.
+    /// Returns false if the map has no jails to send them to.
+    pub(super) fn jail_player(
+        &mut self,
+        player_index: usize,
+        by: usize,
+        events: &mut Vec<GameEvent>,
+    ) -> bool {
+        let jail_team = self.get_player_team(by);
+        let (Some(&jail_x), Some(&jail_y)) =
+            (self.jail_x.get(jail_team), self.jail_y.get(jail_team))
+        else {
+            return false;
+        };
+
+        self.drop_flags(player_index, events);
+        self.player_x[player_index] = Fixed::from_tiles(jail_x as i32);
+        self.player_y[player_index] = Fixed::from_tiles(jail_y as i32);
+        self.jailed[player_index] = true;
+        events.push(GameEvent::PlayerJailed {
+            player: player_index,
+            jail_team,
+        });
+        true
+    }
+
+    /// Frees every prisoner whose teammate is touching the jail holding them.
+    /// Freed players go back to their spawn with spawn protection.
+    pub(super) fn update_jails(&mut self, events: &mut Vec<GameEvent>) {
+        for jail_team in 0..self.jail_x.len() {
+            let jail_x = Fixed::from_tiles(self.jail_x[jail_team] as i32);
+            let jail_y = Fixed::from_tiles(self.jail_y[jail_team] as i32);
+
+            // Prisoners of this jail, grouped by the team that can free them
+            let prisoners: Vec<usize> = (0..self.player_count())
+                .filter(|&player| self.jailed[player] && self.get_player_team(player) != jail_team)
+                .collect();
+
+            for team in 0..self.team_count() {
+                if !prisoners
+                    .iter()
+                    .any(|&player| self.get_player_team(player) == team)
+                {
+                    continue;
+                }
+
+                // Lowest player index gets the credit when several reach the jail together
+                let rescuer = (0..self.player_count()).find(|&player| {
+                    self.get_player_team(player) == team
+                        && self.is_in_play(player)
+                        && self.is_touching_cell(player, jail_x, jail_y)
+                });
+                let Some(by) = rescuer else {
+                    continue;
+                };
+
+                for &player in &prisoners {
+                    if self.get_player_team(player) != team {
+                        continue;
+                    }
+                    self.jailed[player] = false;
+                    self.player_x[player] = self.player_spawn_x[player];
+                    self.player_y[player] = self.player_spawn_y[player];
+                    self.protection_ticks[player] =
+                        self.rules.ticks(self.rules.spawn_protection_secs);
+                    events.push(GameEvent::PlayerRescued { player, by });
+                }
+            }
+        }
+    }
+
+    /// Lets every prisoner go without a rescue, as when a new round starts.
+    pub(super) fn clear_jails(&mut self) {
+        self.jailed.fill(false);
+    }
+
+    fn is_touching_cell(&self, player_index: usize, cell_x: Fixed, cell_y: Fixed) -> bool {
+        let player_left = self.player_x[player_index];
+        let player_top = self.player_y[player_index];
+        let player_right = player_left + self.rules.player_size;
+        let player_bottom = player_top + self.rules.player_size;
+
+        let is_collide_x = player_left < cell_x + Fixed::ONE && cell_x < player_right;
+        let is_collide_y = player_top < cell_y + Fixed::ONE && cell_y < player_bottom;
+        is_collide_x && is_collide_y
+    }
+}
@@ -0,0 +1,550 @@
+//! Map files.
+//!
+//! A map file is a JSON header, a line containing only `---`, and an ASCII
+//! grid describing the arena:
+//!
+//! ```text
+//! {
+//!   "name": "Tiny",
+//!   "teams": ["Blue", "Red"],
+//!   "spawns": [
+//!     { "team": 0, "x": 0, "y": 0 },
+//!     { "team": 1, "x": 5, "y": 0 }
+//!   ],
+//!   "flags": [
+//!     { "team": 0, "x": 0, "y": 2 },
+//!     { "team": 1, "x": 5, "y": 2 }
+//!   ],
+//!   "jails": [
+//!     { "team": 0, "x": 1, "y": 0 },
+//!     { "team": 1, "x": 4, "y": 2 }
+//!   ]
+//! }
+//! ---
+//! ......
+//! ..##..
+//! ......
+//! ```
+//!
+//! Header fields:
+//! - `name`: display name of the map.
+//! - `teams`: two or more team names. The grid is split into one vertical strip
+//!   of territory per team, left to right in declaration order.
+//! - `spawns`: player spawn cells. Players are dealt to teams round-robin and
+//!   each takes the next spawn of their team, wrapping around when a team has
+//!   more players than spawns.
+//! - `flags`: exactly one flag cell per team.
+//! - `jails`: optional. If present, exactly one jail cell per team, holding the
+//!   enemies that team catches. Only maps with jails can be played in jail mode.
+//!
+//! Grid tiles are `.` for floor and `#` for a wall. Every row must have the same
+//! width, and the width must divide evenly between the teams so every territory
+//! is the same size.
+//! Coordinates are zero-based, `x` counting columns and `y` counting rows.
+
+use super::MapDiagnostic;
+use serde::{Deserialize, Serialize};
+use std::{error::Error as StdError, fmt, fs, io, path::Path, str::FromStr};
+
+const CLASSIC: &str = include_str!("../../maps/classic.map");
+
+const HEADER_SEPARATOR: &str = "---";
+const FLOOR_TILE: char = '.';
+const WALL_TILE: char = '#';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Map {
+    name: String,
+    teams: Vec<String>,
+
+    pub(super) player_spawn_x: Vec<usize>,
+    pub(super) player_spawn_y: Vec<usize>,
+    pub(super) player_spawn_team: Vec<usize>,
+
+    pub(super) flag_spawn_x: Vec<usize>,
+    pub(super) flag_spawn_y: Vec<usize>,
+
+    // Empty, or one jail per team
+    pub(super) jail_x: Vec<usize>,
+    pub(super) jail_y: Vec<usize>,
+
+    pub(super) wall_x: Vec<usize>,
+    pub(super) wall_y: Vec<usize>,
+
+    pub(super) width: usize,
+    pub(super) height: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapHeader {
+    name: String,
+    teams: Vec<String>,
+    spawns: Vec<Placement>,
+    flags: Vec<Placement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    jails: Vec<Placement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Placement {
+    team: usize,
+    x: usize,
+    y: usize,
+}
+
+impl Map {
+    /// The 28x14 arena the game shipped with.
+    pub fn classic() -> Self {
+        CLASSIC.parse().expect("built-in classic map is valid")
+    }
+
+    /// Reads and parses a map file from disk.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn teams(&self) -> &[String] {
+        &self.teams
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn has_jails(&self) -> bool {
+        !self.jail_x.is_empty()
+    }
+
+    /// Spawn indices belonging to `team`, in file order.
+    pub(super) fn team_spawns(&self, team: usize) -> Vec<usize> {
+        (0..self.player_spawn_team.len())
+            .filter(|&spawn| self.player_spawn_team[spawn] == team)
+            .collect()
+    }
+}
+
+impl FromStr for Map {
+    type Err = MapError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut lines = source.lines().enumerate();
+
+        let mut header_source = String::new();
+        let mut found_separator = false;
+        for (_, line) in lines.by_ref() {
+            if line.trim() == HEADER_SEPARATOR {
+                found_separator = true;
+                break;
+            }
+            header_source.push_str(line);
+            header_source.push('\n');
+        }
+        if !found_separator {
+            return Err(MapError::MissingSeparator);
+        }
+
+        let header: MapHeader =
+            serde_json::from_str(&header_source).map_err(MapError::InvalidHeader)?;
+
+        let team_count = header.teams.len();
+        if team_count < 2 {
+            return Err(MapError::UnsupportedTeamCount(team_count));
+        }
+
+        // Parse the grid, keeping 1-based file line numbers for error messages
+        let mut rows: Vec<(usize, &str)> = lines
+            .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+            .collect();
+        while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+            rows.pop();
+        }
+
+        let Some(&(_, first_row)) = rows.first() else {
+            return Err(MapError::EmptyGrid);
+        };
+        let width = first_row.chars().count();
+        if width == 0 {
+            return Err(MapError::EmptyGrid);
+        }
+        if width % team_count != 0 {
+            return Err(MapError::UnevenTerritories {
+                width,
+                teams: team_count,
+            });
+        }
+
+        let mut wall_x = Vec::new();
+        let mut wall_y = Vec::new();
+        for (y, (line, row)) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(MapError::RaggedRow {
+                    line: *line,
+                    expected: width,
+                    found,
+                });
+            }
+            for (x, tile) in row.chars().enumerate() {
+                match tile {
+                    FLOOR_TILE => {}
+                    WALL_TILE => {
+                        wall_x.push(x);
+                        wall_y.push(y);
+                    }
+                    tile => {
+                        return Err(MapError::UnknownTile {
+                            line: *line,
+                            column: x + 1,
+                            tile,
+                        });
+                    }
+                }
+            }
+        }
+
+        let check_team = |placement: &Placement| {
+            if placement.team < team_count {
+                Ok(())
+            } else {
+                Err(MapError::UnknownTeam {
+                    team: placement.team,
+                })
+            }
+        };
+
+        for spawn in &header.spawns {
+            check_team(spawn)?;
+        }
+
+        let mut flags: Vec<Option<(usize, usize)>> = vec![None; team_count];
+        for flag in &header.flags {
+            check_team(flag)?;
+            if flags[flag.team].replace((flag.x, flag.y)).is_some() {
+                return Err(MapError::DuplicateFlag { team: flag.team });
+            }
+        }
+        let mut flag_spawn_x = Vec::with_capacity(team_count);
+        let mut flag_spawn_y = Vec::with_capacity(team_count);
+        for (team, flag) in flags.iter().enumerate() {
+            let (x, y) = flag.ok_or(MapError::MissingFlag { team })?;
+            flag_spawn_x.push(x);
+            flag_spawn_y.push(y);
+        }
+
+        let mut jails: Vec<Option<(usize, usize)>> = vec![None; team_count];
+        for jail in &header.jails {
+            check_team(jail)?;
+            if jails[jail.team].replace((jail.x, jail.y)).is_some() {
+                return Err(MapError::DuplicateJail { team: jail.team });
+            }
+        }
+        let mut jail_x = Vec::new();
+        let mut jail_y = Vec::new();
+        if !header.jails.is_empty() {
+            for (team, jail) in jails.iter().enumerate() {
+                let (x, y) = jail.ok_or(MapError::MissingJail { team })?;
+                jail_x.push(x);
+                jail_y.push(y);
+            }
+        }
+
+        Ok(Self {
+            name: header.name,
+            teams: header.teams,
+            player_spawn_x: header.spawns.iter().map(|spawn| spawn.x).collect(),
+            player_spawn_y: header.spawns.iter().map(|spawn| spawn.y).collect(),
+            player_spawn_team: header.spawns.iter().map(|spawn| spawn.team).collect(),
+            flag_spawn_x,
+            flag_spawn_y,
+            jail_x,
+            jail_y,
+            wall_x,
+            wall_y,
+            width,
+            height: rows.len(),
+        })
+    }
+}
+
+/// Writes the map in the file format it was read from, so `map.to_string().parse()`
+/// gives back the same map.
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = MapHeader {
+            name: self.name.clone(),
+            teams: self.teams.clone(),
+            spawns: placements(
+                self.player_spawn_team.iter().copied(),
+                &self.player_spawn_x,
+                &self.player_spawn_y,
+            ),
+            flags: placements(0.., &self.flag_spawn_x, &self.flag_spawn_y),
+            jails: placements(0.., &self.jail_x, &self.jail_y),
+        };
+        let header = serde_json::to_string_pretty(&header).map_err(|_| fmt::Error)?;
+        writeln!(f, "{header}")?;
+        writeln!(f, "{HEADER_SEPARATOR}")?;
+
+        let mut grid = vec![vec![FLOOR_TILE; self.width]; self.height];
+        for (&x, &y) in self.wall_x.iter().zip(self.wall_y.iter()) {
+            grid[y][x] = WALL_TILE;
+        }
+        for row in grid {
+            writeln!(f, "{}", row.into_iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+fn placements(teams: impl Iterator<Item = usize>, xs: &[usize], ys: &[usize]) -> Vec<Placement> {
+    teams
+        .zip(xs.iter().zip(ys.iter()))
+        .map(|(team, (&x, &y))| Placement { team, x, y })
+        .collect()
+}
+
+/// Errors produced while loading a map or building a game from one.
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    MissingSeparator,
+    InvalidHeader(serde_json::Error),
+    UnsupportedTeamCount(usize),
+    UnknownTeam {
+        team: usize,
+    },
+    MissingFlag {
+        team: usize,
+    },
+    DuplicateFlag {
+        team: usize,
+    },
+    MissingJail {
+        team: usize,
+    },
+    DuplicateJail {
+        team: usize,
+    },
+    EmptyGrid,
+    UnevenTerritories {
+        width: usize,
+        teams: usize,
+    },
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownTile {
+        line: usize,
+        column: usize,
+        tile: char,
+    },
+    Invalid(Vec<MapDiagnostic>),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Io(err) => write!(f, "could not read map file: {err}"),
+            MapError::MissingSeparator => {
+                write!(
+                    f,
+                    "missing `{HEADER_SEPARATOR}` line between header and grid"
+                )
+            }
+            MapError::InvalidHeader(err) => write!(f, "invalid map header: {err}"),
+            MapError::UnsupportedTeamCount(count) => {
+                write!(f, "maps need at least 2 teams, found {count}")
+            }
+            MapError::UnknownTeam { team } => write!(f, "team {team} is not declared in `teams`"),
+            MapError::MissingFlag { team } => write!(f, "team {team} has no flag"),
+            MapError::DuplicateFlag { team } => write!(f, "team {team} has more than one flag"),
+            MapError::MissingJail { team } => {
+                write!(f, "team {team} has no jail, but other teams do")
+            }
+            MapError::DuplicateJail { team } => write!(f, "team {team} has more than one jail"),
+            MapError::EmptyGrid => write!(f, "map grid is empty"),
+            MapError::UnevenTerritories { width, teams } => write!(
+                f,
+                "map width {width} cannot be split evenly between {teams} teams"
+            ),
+            MapError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: expected a row of {expected} tiles, found {found}"
+            ),
+            MapError::UnknownTile { line, column, tile } => {
+                write!(f, "line {line}, column {column}: unknown tile {tile:?}")
+            }
+            MapError::Invalid(diagnostics) => {
+                write!(f, "map failed validation: ")?;
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{diagnostic}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl StdError for MapError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            MapError::Io(err) => Some(err),
+            MapError::InvalidHeader(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MapError {
+    fn from(err: io::Error) -> Self {
+        MapError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = r#"{
+        "name": "Tiny",
+        "teams": ["Blue", "Red"],
+        "spawns": [{ "team": 0, "x": 0, "y": 0 }, { "team": 1, "x": 5, "y": 0 }],
+        "flags": [{ "team": 0, "x": 0, "y": 2 }, { "team": 1, "x": 5, "y": 2 }]
+    }"#;
+
+    fn tiny(grid: &str) -> Result<Map, MapError> {
+        format!("{HEADER}\n---\n{grid}").parse()
+    }
+
+    #[test]
+    fn parses_classic_map() {
+        let map = Map::classic();
+        assert_eq!(map.name(), "Classic");
+        assert_eq!((map.width(), map.height()), (28, 14));
+        assert_eq!(map.team_spawns(0), vec![0, 2]);
+        assert_eq!(map.team_spawns(1), vec![1, 3]);
+        assert_eq!(map.flag_spawn_x, [0, 27]);
+        assert_eq!(map.flag_spawn_y, [7, 6]);
+        assert_eq!(map.jail_x, [3, 24]);
+        assert_eq!(map.jail_y, [11, 2]);
+        assert_eq!(
+            map.wall_x,
+            vec![0, 27, 8, 19, 8, 9, 18, 19, 8, 9, 18, 19, 8, 19, 0, 27]
+        );
+        assert_eq!(
+            map.wall_y,
+            vec![1, 1, 2, 2, 6, 6, 6, 6, 7, 7, 7, 7, 11, 11, 12, 12]
+        );
+    }
+
+    #[test]
+    fn parses_grid_and_ignores_trailing_blank_lines() {
+        let map = tiny("......\r\n..##..\n......\n\n").unwrap();
+        assert_eq!((map.width(), map.height()), (6, 3));
+        assert_eq!(map.wall_x, vec![2, 3]);
+        assert_eq!(map.wall_y, vec![1, 1]);
+        assert_eq!(map.player_spawn_team, vec![0, 1]);
+    }
+
+    #[test]
+    fn jails_are_optional_but_needed_for_every_team() {
+        assert!(!tiny("......").unwrap().has_jails());
+
+        let with_jails = |jails: &str| {
+            let header = HEADER.replace("\n    }", &format!(",\n\"jails\": [{jails}]\n    }}"));
+            format!("{header}\n---\n......").parse::<Map>()
+        };
+        let map = with_jails(r#"{ "team": 0, "x": 1, "y": 0 }, { "team": 1, "x": 4, "y": 0 }"#);
+        assert!(map.unwrap().has_jails());
+        assert!(matches!(
+            with_jails(r#"{ "team": 1, "x": 4, "y": 0 }"#),
+            Err(MapError::MissingJail { team: 0 })
+        ));
+        assert!(matches!(
+            with_jails(r#"{ "team": 0, "x": 1, "y": 0 }, { "team": 0, "x": 2, "y": 0 }"#),
+            Err(MapError::DuplicateJail { team: 0 })
+        ));
+    }
+
+    #[test]
+    fn writes_maps_back_in_file_format() {
+        let classic = Map::classic();
+        assert_eq!(classic.to_string().parse::<Map>().unwrap(), classic);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(matches!(
+            "......".parse::<Map>(),
+            Err(MapError::MissingSeparator)
+        ));
+        assert!(matches!(
+            "{ \"name\": 1 }\n---\n..".parse::<Map>(),
+            Err(MapError::InvalidHeader(_))
+        ));
+        assert!(matches!(tiny(""), Err(MapError::EmptyGrid)));
+        assert!(matches!(
+            tiny("....."),
+            Err(MapError::UnevenTerritories { width: 5, teams: 2 })
+        ));
+        assert!(matches!(
+            tiny("......\n....\n"),
+            Err(MapError::RaggedRow {
+                line: 9,
+                expected: 6,
+                found: 4
+            })
+        ));
+        assert!(matches!(
+            tiny("......\n..x...\n"),
+            Err(MapError::UnknownTile {
+                line: 9,
+                column: 3,
+                tile: 'x'
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_teams_and_flags() {
+        let one_flag = HEADER.replace(r#", { "team": 1, "x": 5, "y": 2 }"#, "");
+        assert!(matches!(
+            format!("{one_flag}\n---\n......").parse::<Map>(),
+            Err(MapError::MissingFlag { team: 1 })
+        ));
+
+        let unknown_team = HEADER.replace(
+            r#""team": 1, "x": 5, "y": 0"#,
+            r#""team": 2, "x": 5, "y": 0"#,
+        );
+        assert!(matches!(
+            format!("{unknown_team}\n---\n......").parse::<Map>(),
+            Err(MapError::UnknownTeam { team: 2 })
+        ));
+
+        let one_team = HEADER.replace(r#"["Blue", "Red"]"#, r#"["Blue"]"#);
+        assert!(matches!(
+            format!("{one_team}\n---\n......").parse::<Map>(),
+            Err(MapError::UnsupportedTeamCount(1))
+        ));
+    }
+}
@@ -1,190 +1,245 @@
+mod collision;
+mod event;
+mod fixed;
+mod flag;
+mod hash;
+mod jail;
+mod map;
+mod phase;
 mod player_move;
-
-use core::f32;
-
+mod replay;
+mod respawn;
+mod rules;
+#[cfg(test)]
+mod test_game;
+mod validate;
+
+pub use collision::Tag;
+pub use event::GameEvent;
+pub use fixed::Fixed;
+use flag::DroppedFlag;
+pub use flag::{FlagState, FlagStatus};
 use itertools::Itertools;
+pub use map::{Map, MapError};
+pub use phase::MatchPhase;
 pub use player_move::Move;
+pub use replay::{Replay, ReplayError, ReplayWriter};
+pub use rules::{GameRules, TagBehavior};
+pub use validate::{MapDiagnostic, MapEntity, Severity, Symmetry};
 
-#[derive(Debug, Clone)]
-struct Map<const N: usize> {
-    player_spawn_x: [usize; N],
-    player_spawn_y: [usize; N],
+#[derive(Debug, Clone, Hash)]
+pub struct GameState {
+    team_names: Vec<String>,
+    scores: Vec<usize>,
 
-    flag_spawn_x: [usize; 2],
-    flag_spawn_y: [usize; 2],
+    player_spawn_x: Vec<Fixed>,
+    player_spawn_y: Vec<Fixed>,
 
-    wall_x: Vec<usize>,
-    wall_y: Vec<usize>,
+    player_x: Vec<Fixed>,
+    player_y: Vec<Fixed>,
+
+    player_team: Vec<usize>,
 
     width: usize,
     height: usize,
-}
 
-// TODO: implement dynamically loading maps
-impl<const N: usize> Map<N> {
-    const WIDTH: usize = 28;
-    const HEIGHT: usize = 14;
+    flag_spawn_x: Vec<usize>,
+    flag_spawn_y: Vec<usize>,
 
-    pub fn new() -> Self {
-        assert!(N == 2 || N == 4, "A map must have either 2 or 4 players");
-        assert!(Self::WIDTH % 2 == 0, "A map must have an even width");
+    wall_x: Vec<usize>,
+    wall_y: Vec<usize>,
 
-        let mut player_spawn_x = [0; N];
-        let mut player_spawn_y = [0; N];
+    // One jail per team, or none if the map has no jails
+    jail_x: Vec<usize>,
+    jail_y: Vec<usize>,
+    jailed: Vec<bool>,
 
-        match N {
-            2 => {
-                player_spawn_x.copy_from_slice(&[0, Self::WIDTH - 1]);
-                player_spawn_y.copy_from_slice(&[0, 0]);
-            }
-            4 => {
-                player_spawn_x.copy_from_slice(&[0, 27, 0, Self::WIDTH - 1]);
-                player_spawn_y.copy_from_slice(&[0, 0, Self::HEIGHT - 1, Self::HEIGHT - 1]);
-            }
-            _ => unreachable!(),
-        }
+    // Index of the player holding each team's flag
+    // Ex. Player 3 holding the flag of team 0 -> [Some(3), None]
+    flag_captors: Vec<Option<usize>>,
 
-        Self {
-            player_spawn_x,
-            player_spawn_y,
-            width: Self::WIDTH,
-            height: Self::HEIGHT,
-            flag_spawn_x: [0, Self::WIDTH - 1],
-            flag_spawn_y: [Self::HEIGHT / 2, Self::HEIGHT / 2 - 1], // -1 to make it symmetrical
-            wall_x: vec![0, 27, 8, 19, 8, 9, 18, 19, 8, 9, 18, 19, 8, 19, 0, 27],
-            wall_y: vec![1, 1, 2, 2, 6, 6, 6, 6, 7, 7, 7, 7, 11, 11, 12, 12],
-        }
-    }
-}
+    // Flags lying where their carrier was tagged, per team
+    dropped_flags: Vec<Option<DroppedFlag>>,
 
-#[derive(Debug, Clone)]
-pub struct GameState<const N: usize> {
-    scores: [usize; 2],
+    // Ticks each tagged player still sits out at their spawn
+    respawn_ticks: Vec<u32>,
+    // Ticks each respawned player still cannot be tagged
+    protection_ticks: Vec<u32>,
 
-    player_spawn_x: [f32; N],
-    player_spawn_y: [f32; N],
+    rules: GameRules,
+    phase: MatchPhase,
+    overtime: bool,
+    // Ticks spent playing, counted against the time limit
+    ticks: u32,
+}
 
-    player_x: [f32; N],
-    player_y: [f32; N],
+/// Most players a game can have on each team.
+pub const MAX_TEAM_SIZE: usize = 16;
 
-    width: usize,
-    height: usize,
+impl GameState {
+    const WALL_SIZE: Fixed = Fixed::ONE;
 
-    flag_spawn_x: [usize; 2],
-    flag_spawn_y: [usize; 2],
+    const DEFAULT_TEAM_SIZE: usize = 2;
 
-    wall_x: Vec<usize>,
-    wall_y: Vec<usize>,
+    /// A 2v2 game on the classic map.
+    pub fn new() -> Self {
+        Self::from_map(&Map::classic(), Self::DEFAULT_TEAM_SIZE).expect("classic map is valid")
+    }
 
-    // Index of the player holding the flag
-    // Ex. Player 3 holding the flag of team 0 -> [Some(3), None]
-    flag_captors: [Option<usize>; 2],
-}
+    /// Builds a game on `map` with `team_size` players on each of the map's teams,
+    /// played with the default [`GameRules`].
+    ///
+    /// Players are dealt to teams round-robin, so player `i` is on team
+    /// `i % teams`, and each takes the next spawn of their team. Maps with
+    /// [`Severity::Error`] diagnostics are rejected.
+    pub fn from_map(map: &Map, team_size: usize) -> Result<Self, MapError> {
+        let errors: Vec<MapDiagnostic> = map
+            .validate()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .collect();
+        if !errors.is_empty() {
+            return Err(MapError::Invalid(errors));
+        }
 
-impl<const N: usize> GameState<N> {
-    const PLAYER_SPEED: f32 = 0.25;
-    const PLAYER_SIZE: f32 = 1.0;
-    const FLAG_SIZE: f32 = 1.0;
-    const WALL_SIZE: f32 = 1.0;
+        let team_count = map.teams().len();
+        let team_spawns: Vec<Vec<usize>> =
+            (0..team_count).map(|team| map.team_spawns(team)).collect();
+
+        let player_count = team_count * team_size;
+        let mut player_spawn_x = Vec::with_capacity(player_count);
+        let mut player_spawn_y = Vec::with_capacity(player_count);
+        let mut player_team = Vec::with_capacity(player_count);
+        for player_index in 0..player_count {
+            let team = player_index % team_count;
+            let spawns = &team_spawns[team];
+            let spawn = spawns[(player_index / team_count) % spawns.len()];
+
+            player_spawn_x.push(Fixed::from_tiles(map.player_spawn_x[spawn] as i32));
+            player_spawn_y.push(Fixed::from_tiles(map.player_spawn_y[spawn] as i32));
+            player_team.push(team);
+        }
 
-    pub fn new() -> Self {
-        assert!(N == 2 || N == 4, "A game must have either 2 or 4 players");
-        let Map {
-            width,
-            height,
+        Ok(Self {
+            team_names: map.teams().to_vec(),
+            scores: vec![0; team_count],
+            width: map.width,
+            height: map.height,
+            player_x: player_spawn_x.clone(),
+            player_y: player_spawn_y.clone(),
             player_spawn_x,
             player_spawn_y,
-            flag_spawn_x,
-            flag_spawn_y,
-            wall_x,
-            wall_y,
-        } = Map::<N>::new();
-
-        let player_spawn_x = player_spawn_x.map(|x| x as f32);
-        let player_spawn_y = player_spawn_y.map(|y| y as f32);
-
-        Self {
-            scores: [0; 2],
-            width,
-            height,
-            player_spawn_x: player_spawn_x,
-            player_spawn_y: player_spawn_y,
-            player_x: player_spawn_x,
-            player_y: player_spawn_y,
-            flag_spawn_x,
-            flag_spawn_y,
-            wall_y,
-            wall_x,
-            flag_captors: [None; 2],
-        }
+            player_team,
+            flag_spawn_x: map.flag_spawn_x.clone(),
+            flag_spawn_y: map.flag_spawn_y.clone(),
+            wall_x: map.wall_x.clone(),
+            wall_y: map.wall_y.clone(),
+            jail_x: map.jail_x.clone(),
+            jail_y: map.jail_y.clone(),
+            jailed: vec![false; player_count],
+            flag_captors: vec![None; team_count],
+            dropped_flags: vec![None; team_count],
+            respawn_ticks: vec![0; player_count],
+            protection_ticks: vec![0; player_count],
+            rules: GameRules::default(),
+            phase: MatchPhase::Lobby,
+            overtime: false,
+            ticks: 0,
+        })
+    }
+
+    /// Plays the game with `rules` instead of the defaults. The rules are expected
+    /// to have passed [`GameRules::validate`].
+    pub fn with_rules(mut self, rules: GameRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.player_team.len()
+    }
+
+    pub fn team_name(&self, team_index: usize) -> &str {
+        &self.team_names[team_index]
+    }
+
+    pub fn team_count(&self) -> usize {
+        self.team_names.len()
     }
 
     // Returns player index of the captor of team_index's flag
     fn get_flag_captor(&self, team_index: usize) -> Option<usize> {
-        assert!(
-            team_index == 0 || team_index == 1,
-            "Team must be either 0 or 1"
-        );
         self.flag_captors[team_index]
     }
 
-    fn get_player_team(&self, player_index: usize) -> usize {
-        player_index % 2
+    pub fn get_player_team(&self, player_index: usize) -> usize {
+        self.player_team[player_index]
     }
 
-    fn get_is_player_on_home_side(&self, player_index: usize, left: f32) -> bool {
+    // Each team owns an equal vertical strip of the map, in team order from the left
+    fn get_is_player_on_home_side(&self, player_index: usize, left: Fixed) -> bool {
         let team_index = self.get_player_team(player_index);
-        let center_x_pos = left + GameState::<N>::PLAYER_SIZE / 2.0;
-        let is_on_left_side = center_x_pos < (self.width as f32) / 2.0;
-        (team_index == 0) && is_on_left_side || (team_index == 1) && !is_on_left_side
+        let center_x_pos = left + self.rules.player_size.half();
+        let territory_width = (self.width / self.team_count()) as i32;
+        let territory =
+            ((center_x_pos.floor_tiles() / territory_width) as usize).min(self.team_count() - 1);
+        territory == team_index
     }
 
-    fn reset_player(&mut self, player_index: usize) {
+    fn reset_player(&mut self, player_index: usize, events: &mut Vec<GameEvent>) {
+        // The flag falls where the carrier was caught, not at their spawn
+        self.drop_flags(player_index, events);
+
         let reset_x = self.player_spawn_x[player_index];
         let reset_y = self.player_spawn_y[player_index];
 
         self.player_x[player_index] = reset_x;
         self.player_y[player_index] = reset_y;
-
-        let flag_captors = self.flag_captors;
-        for (team_index, flag_captor) in flag_captors.iter().enumerate() {
-            if let Some(flag_captor) = flag_captor {
-                if *flag_captor == player_index {
-                    self.flag_captors[team_index] = None;
-                }
-            }
-        }
+        self.start_respawn(player_index);
     }
 
-    pub fn step(&mut self, player_moves: [Move; N]) -> Vec<usize> {
-        // Debug: Print current positions before processing
-        println!("=== GAME STEP ===");
-        for i in 0..N {
-            println!(
-                "Player {}: ({:.2}, {:.2}) Team: {}",
-                i,
-                self.player_x[i],
-                self.player_y[i],
-                self.get_player_team(i)
-            );
+    /// Advances the game by one tick. `player_moves` holds one move per player.
+    /// Returns everything that happened during the tick.
+    pub fn step(&mut self, player_moves: &[Move]) -> Vec<GameEvent> {
+        assert_eq!(
+            player_moves.len(),
+            self.player_count(),
+            "A move is needed for every player"
+        );
+
+        let mut events = Vec::new();
+        if !self.advance_phase(&mut events) {
+            return events;
         }
-        println!("Flag captors: {:?}", self.flag_captors);
+        self.ticks += 1;
+        let running_timers = self.running_timers();
 
         for (player_index, player_move) in player_moves.iter().enumerate() {
-            let (player_dx, player_dy) = player_move.to_coords();
+            if !self.is_in_play(player_index) {
+                continue;
+            }
+
+            let (mut player_dx, mut player_dy) = player_move.to_coords();
+            if !self.rules.allow_diagonals && player_dx != 0 && player_dy != 0 {
+                (player_dx, player_dy) = (0, 0);
+            }
 
             let player_x = self.player_x[player_index];
             let player_y = self.player_y[player_index];
 
-            let speed = GameState::<N>::PLAYER_SPEED;
+            let speed = self.rules.player_step();
 
             // Calculate new position with bounds checking
-            let new_x = (player_x + ((player_dx as f32) * speed))
-                .max(0.0)
-                .min((self.width as f32) - GameState::<N>::PLAYER_SIZE);
-            let new_y = (player_y + ((player_dy as f32) * speed))
-                .max(0.0)
-                .min((self.height as f32) - GameState::<N>::PLAYER_SIZE);
+            let new_x = (player_x + speed * player_dx)
+                .max(Fixed::ZERO)
+                .min(Fixed::from_tiles(self.width as i32) - self.rules.player_size);
+            let new_y = (player_y + speed * player_dy)
+                .max(Fixed::ZERO)
+                .min(Fixed::from_tiles(self.height as i32) - self.rules.player_size);
 
             // Check if the new position would collide with walls
             if !self.would_collide_with_walls(new_x, new_y) {
@@ -202,104 +257,40 @@ impl<const N: usize> GameState<N> {
                     self.player_y[player_index] = new_y;
                 }
             }
-        }
 
-        // Handle player collisions
-        let players_to_reset_moves = Vec::new();
-        let player_indices: [usize; N] = std::array::from_fn(|i| i);
-        for pair in player_indices.iter().combinations(2) {
-            let player_index_0 = *pair[0];
-            let player_index_1 = *pair[1];
-
-            if self.get_player_team(player_index_0) == self.get_player_team(player_index_1) {
-                continue;
+            let (x, y) = (self.player_x[player_index], self.player_y[player_index]);
+            if (x, y) != (player_x, player_y) {
+                events.push(GameEvent::PlayerMoved {
+                    player: player_index,
+                    x: x.to_f32(),
+                    y: y.to_f32(),
+                });
             }
+        }
 
-            let player_0_x = self.player_x[player_index_0];
-            let player_0_y = self.player_y[player_index_0];
-            let player_1_x = self.player_x[player_index_1];
-            let player_1_y = self.player_y[player_index_1];
-
-            let player_0_left = player_0_x;
-            let player_0_top = player_0_y;
-            let player_0_right = player_0_left + GameState::<N>::PLAYER_SIZE;
-            let player_0_bottom = player_0_top + GameState::<N>::PLAYER_SIZE;
-
-            let player_1_left = player_1_x;
-            let player_1_top = player_1_y;
-            let player_1_right = player_1_left + GameState::<N>::PLAYER_SIZE;
-            let player_1_bottom = player_1_top + GameState::<N>::PLAYER_SIZE;
-
-            let is_collide_x = player_0_left < player_1_right && player_1_left < player_0_right;
-            let is_collide_y = player_0_top < player_1_bottom && player_1_top < player_0_bottom;
-            let is_collide = is_collide_x && is_collide_y;
-
-            if !is_collide {
+        // Handle player collisions
+        let tags = match self.rules.tag_behavior {
+            TagBehavior::Respawn | TagBehavior::Jail => self.find_tags(),
+            TagBehavior::Off => Vec::new(),
+        };
+        for Tag { player, by } in tags {
+            events.push(GameEvent::PlayerTagged { player, by });
+            if self.rules.tag_behavior == TagBehavior::Jail
+                && self.jail_player(player, by, &mut events)
+            {
                 continue;
             }
-
-            // Track which players need their moves reset
-            let mut players_to_reset_moves = Vec::new();
-
-            let actual_indices = [player_index_0, player_index_1];
-            for (i, left) in [player_0_left, player_1_left].iter().enumerate() {
-                let player_index = actual_indices[i];
-                if !self.get_is_player_on_home_side(player_index, *left) {
-                    self.reset_player(player_index);
-                    players_to_reset_moves.push(player_index);
-                    players_to_reset_moves.push(player_index);
-                }
-            }
-
-            // Return the list of players whose moves should be reset
-            return players_to_reset_moves;
+            self.reset_player(player, &mut events);
         }
 
-        // No players need move reset - continue with flag captures
-
-        // Handle flag captures
-        for player_index in player_indices {
-            let player_x = self.player_x[player_index];
-            let player_y = self.player_y[player_index];
-            let player_team = self.get_player_team(player_index);
-
-            for team_index in [0, 1] {
-                // Skip if trying to capture own team's flag
-                if player_team == team_index {
-                    continue;
-                }
+        // Handle jailbreaks
+        self.update_jails(&mut events);
 
-                // Skip if flag is already captured
-                if self.get_flag_captor(team_index).is_some() {
-                    continue;
-                }
-
-                let flag_spawn_x = self.flag_spawn_x[team_index] as f32;
-                let flag_spawn_y = self.flag_spawn_y[team_index] as f32;
-
-                // Calculate distance between player center and flag center
-                let player_center_x = player_x + GameState::<N>::PLAYER_SIZE / 2.0;
-                let player_center_y = player_y + GameState::<N>::PLAYER_SIZE / 2.0;
-                let flag_center_x = flag_spawn_x + GameState::<N>::FLAG_SIZE / 2.0;
-                let flag_center_y = flag_spawn_y + GameState::<N>::FLAG_SIZE / 2.0;
-
-                let distance = ((player_center_x - flag_center_x).powi(2)
-                    + (player_center_y - flag_center_y).powi(2))
-                .sqrt();
-                let capture_distance = 1.2; // Generous capture distance
-
-                if distance <= capture_distance {
-                    self.flag_captors[team_index] = Some(player_index);
-                    println!(
-                        "🚩 Player {} captured team {}'s flag! Distance: {:.2}",
-                        player_index, team_index, distance
-                    );
-                }
-            }
-        }
+        // Handle flag pickups, returns and auto-returns
+        self.update_flags(&mut events);
 
         // Handle score
-        let flag_captors = self.flag_captors;
+        let flag_captors = self.flag_captors.clone();
         for (team_index, flag_captor) in flag_captors.iter().enumerate() {
             if let Some(player_index) = flag_captor {
                 let left = self.player_x[*player_index];
@@ -307,39 +298,43 @@ impl<const N: usize> GameState<N> {
                     self.flag_captors[team_index] = None;
                     let scoring_team_index = self.get_player_team(*player_index);
                     self.scores[scoring_team_index] += 1;
-                    println!(
-                        "🎯 SCORE! Player {} scored for team {}! New scores: {:?}",
-                        player_index, scoring_team_index, self.scores
-                    );
-
-                    // Reset all players to spawn positions after a score
-                    for i in 0..N {
-                        self.player_x[i] = self.player_spawn_x[i];
-                        self.player_y[i] = self.player_spawn_y[i];
-                    }
-                    println!("🔄 All players reset to spawn positions after score!");
+                    events.push(GameEvent::Scored {
+                        team: scoring_team_index,
+                        player: *player_index,
+                    });
+
+                    // Reset all players and flags to spawn positions after a score
+                    self.player_x.clone_from(&self.player_spawn_x);
+                    self.player_y.clone_from(&self.player_spawn_y);
+                    self.dropped_flags.fill(None);
+                    self.clear_respawns();
+                    self.clear_jails();
+                    events.push(GameEvent::RoundReset);
                 }
             }
         }
 
-        println!("Final flag captors: {:?}", self.flag_captors);
-        println!("=================");
+        self.update_respawns(&running_timers, &mut events);
+
+        let scored = events
+            .iter()
+            .any(|event| matches!(event, GameEvent::Scored { .. }));
+        self.update_match(scored, &mut events);
 
-        // Return list of players whose moves should be reset
-        players_to_reset_moves
+        events
     }
 
-    fn would_collide_with_walls(&self, x: f32, y: f32) -> bool {
+    fn would_collide_with_walls(&self, x: Fixed, y: Fixed) -> bool {
         let player_left = x;
         let player_top = y;
-        let player_right = x + GameState::<N>::PLAYER_SIZE;
-        let player_bottom = y + GameState::<N>::PLAYER_SIZE;
+        let player_right = x + self.rules.player_size;
+        let player_bottom = y + self.rules.player_size;
 
         for (wall_x, wall_y) in self.wall_x.iter().zip(self.wall_y.iter()) {
-            let wall_left = *wall_x as f32;
-            let wall_top = *wall_y as f32;
-            let wall_right = wall_left + GameState::<N>::WALL_SIZE;
-            let wall_bottom = wall_top + GameState::<N>::WALL_SIZE;
+            let wall_left = Fixed::from_tiles(*wall_x as i32);
+            let wall_top = Fixed::from_tiles(*wall_y as i32);
+            let wall_right = wall_left + GameState::WALL_SIZE;
+            let wall_bottom = wall_top + GameState::WALL_SIZE;
 
             let is_collide_x = player_left < wall_right && wall_left < player_right;
             let is_collide_y = player_top < wall_bottom && wall_top < player_bottom;
@@ -351,36 +346,45 @@ impl<const N: usize> GameState<N> {
         false
     }
 
+    /// Player positions in tiles, for display.
     pub fn positions(&self) -> Vec<(f32, f32)> {
-        (0..N)
-            .map(|i| (self.player_x[i], self.player_y[i]))
+        (0..self.player_count())
+            .map(|i| (self.player_x[i].to_f32(), self.player_y[i].to_f32()))
             .collect()
     }
 
-    pub fn get_flag_captors(&self) -> [Option<usize>; 2] {
-        self.flag_captors
+    pub fn get_flag_captors(&self) -> &[Option<usize>] {
+        &self.flag_captors
     }
 
-    pub fn get_scores(&self) -> [usize; 2] {
-        self.scores
+    pub fn get_scores(&self) -> &[usize] {
+        &self.scores
     }
 
     pub fn pretty_print(&self) {
-        print!(
-            "Blue {:>2} {:->15} Red {:>2}\r\n",
-            self.scores[0], "", self.scores[1]
-        );
+        let header = self
+            .team_names
+            .iter()
+            .zip(self.scores.iter())
+            .map(|(name, score)| format!("{name} {score:>2}"))
+            .join(&format!(" {:->15} ", ""));
+        print!("{header}\r\n");
 
         let mut grid: Vec<Vec<char>> = vec![vec![' '; self.width]; self.height];
 
         print!("┌{:->width$}┐\r\n", "", width = self.width);
 
+        // Print jails
+        for (x, y) in self.jails() {
+            grid[y][x] = '▒';
+        }
+
         // Print players
         let player_x_iter = self.player_x.iter();
         let player_y_iter = self.player_y.iter();
         for (i, (x, y)) in player_x_iter.zip(player_y_iter).enumerate() {
-            let x_rounded = x.floor() as usize;
-            let y_rounded = y.floor() as usize;
+            let x_rounded = x.floor_tiles() as usize;
+            let y_rounded = y.floor_tiles() as usize;
             grid[y_rounded][x_rounded] = i.to_string().chars().next().unwrap_or('_');
         }
 
@@ -392,17 +396,23 @@ impl<const N: usize> GameState<N> {
         }
 
         // Print flags
-        for team_index in [0, 1] {
-            let flag_char = match team_index {
-                0 => 'B',
-                1 => 'R',
-                _ => unreachable!("Team index must be 0 or 1"),
-            };
+        for team_index in 0..self.team_count() {
+            let flag_char = self.team_names[team_index]
+                .chars()
+                .next()
+                .map_or('F', |c| c.to_ascii_uppercase());
             let flag_captor = self.get_flag_captor(team_index);
-            match flag_captor {
+            match (flag_captor, self.dropped_flags[team_index]) {
                 // TODO: draw when it's been captured
-                Some(_player_index) => (),
-                None => {
+                (Some(_player_index), _) => (),
+                // Dropped flags are drawn in lowercase
+                (None, Some(dropped)) => {
+                    let flag_x = dropped.x.floor_tiles() as usize;
+                    let flag_y = dropped.y.floor_tiles() as usize;
+
+                    grid[flag_y][flag_x] = flag_char.to_ascii_lowercase();
+                }
+                (None, None) => {
                     let flag_x = self.flag_spawn_x[team_index];
                     let flag_y = self.flag_spawn_y[team_index];
 
@@ -420,29 +430,98 @@ impl<const N: usize> GameState<N> {
     }
 }
 
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod dev_test {
+    use super::test_game::{new_game, playing};
     use super::*;
 
     #[test]
     fn quick_dev() {
-        let mut game = GameState::<4>::new();
+        let mut game = playing(new_game());
         game.pretty_print();
-        for _ in (0..104) {
-            game.step([Move::Right, Move::Stay, Move::Stay, Move::Stay]);
+        for _ in 0..104 {
+            game.step(&[Move::Right, Move::Stay, Move::Stay, Move::Stay]);
         }
-        for _ in (0..24) {
-            game.step([Move::Down, Move::Stay, Move::Stay, Move::Stay]);
+        for _ in 0..24 {
+            game.step(&[Move::Down, Move::Stay, Move::Stay, Move::Stay]);
         }
-        for _ in (0..4) {
-            game.step([Move::Right, Move::Stay, Move::Stay, Move::Stay]);
+        for _ in 0..4 {
+            game.step(&[Move::Right, Move::Stay, Move::Stay, Move::Stay]);
         }
-        for _ in (0..4) {
-            game.step([Move::Up, Move::Stay, Move::Stay, Move::Stay]);
+        for _ in 0..4 {
+            game.step(&[Move::Up, Move::Stay, Move::Stay, Move::Stay]);
         }
-        for _ in (0..96) {
-            game.step([Move::Left, Move::Stay, Move::Stay, Move::Stay]);
+        for _ in 0..96 {
+            game.step(&[Move::Left, Move::Stay, Move::Stay, Move::Stay]);
         }
         game.pretty_print();
     }
+
+    #[test]
+    fn step_reports_capture_and_score_events() {
+        let mut game = playing(new_game());
+        let mut events = Vec::new();
+        let path = [
+            (Move::Right, 104),
+            (Move::Down, 24),
+            (Move::Right, 4),
+            (Move::Up, 4),
+            (Move::Left, 96),
+        ];
+        for (player_move, ticks) in path {
+            for _ in 0..ticks {
+                events.extend(game.step(&[player_move, Move::Stay, Move::Stay, Move::Stay]));
+            }
+        }
+
+        let milestones: Vec<&GameEvent> = events
+            .iter()
+            .filter(|event| !matches!(event, GameEvent::PlayerMoved { .. }))
+            .collect();
+        assert_eq!(
+            milestones,
+            vec![
+                &GameEvent::FlagPicked { team: 1, player: 0 },
+                &GameEvent::Scored { team: 0, player: 0 },
+                &GameEvent::RoundReset,
+                &GameEvent::PhaseChanged {
+                    phase: MatchPhase::RoundOver { ticks_left: 10 }
+                },
+                &GameEvent::PhaseChanged {
+                    phase: MatchPhase::Countdown { ticks_left: 15 }
+                },
+                &GameEvent::PhaseChanged {
+                    phase: MatchPhase::Playing
+                },
+            ]
+        );
+        assert!(events.contains(&GameEvent::PlayerMoved {
+            player: 0,
+            x: 0.25,
+            y: 0.0
+        }));
+    }
+
+    #[test]
+    fn sizes_games_from_team_size() {
+        let one_v_one = GameState::from_map(&Map::classic(), 1).unwrap();
+        assert_eq!(one_v_one.positions(), vec![(0.0, 0.0), (27.0, 0.0)]);
+
+        // Classic has two spawns per team, so the third player of each team wraps around
+        let three_v_three = GameState::from_map(&Map::classic(), 3).unwrap();
+        assert_eq!(three_v_three.player_count(), 6);
+        assert_eq!(three_v_three.player_team, vec![0, 1, 0, 1, 0, 1]);
+        assert_eq!(three_v_three.positions()[4], (0.0, 0.0));
+        assert_eq!(three_v_three.positions()[5], (27.0, 0.0));
+
+        let mut five_v_five = playing(GameState::from_map(&Map::classic(), 5).unwrap());
+        five_v_five.step(&[Move::Right; 10]);
+        assert_eq!(five_v_five.get_scores(), &[0, 0]);
+    }
 }
@@ -0,0 +1,143 @@
+use super::{GameEvent, GameState};
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Where a match is in its lifecycle. Players can only move while `Playing`.
+///
+/// `Lobby -> Countdown -> Playing -> RoundOver -> Countdown -> ... -> MatchOver`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum MatchPhase {
+    /// Waiting for the host to start the match.
+    Lobby,
+    /// Players wait at their spawns for `ticks_left` more ticks.
+    Countdown {
+        ticks_left: u32,
+    },
+    Playing,
+    /// A team just scored. The next countdown starts in `ticks_left` ticks.
+    RoundOver {
+        ticks_left: u32,
+    },
+    MatchOver,
+}
+
+impl GameState {
+    pub fn phase(&self) -> MatchPhase {
+        self.phase
+    }
+
+    /// Whether the time limit ran out on a tie and the next score wins.
+    pub fn is_overtime(&self) -> bool {
+        self.overtime
+    }
+
+    /// Whether the match has been won. A finished game ignores further steps.
+    pub fn is_finished(&self) -> bool {
+        self.phase == MatchPhase::MatchOver
+    }
+
+    /// Ticks of regulation time left, if there is a time limit.
+    pub fn remaining_ticks(&self) -> Option<u32> {
+        self.rules
+            .time_limit_secs
+            .map(|limit| self.rules.ticks(limit).saturating_sub(self.ticks))
+    }
+
+    /// Leaves the lobby and starts the first countdown.
+    /// Does nothing once the match has started.
+    pub fn start(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.phase == MatchPhase::Lobby {
+            self.start_countdown(&mut events);
+        }
+        events
+    }
+
+    /// Counts down the phase the game is waiting in.
+    /// Returns whether players can move this tick.
+    pub(super) fn advance_phase(&mut self, events: &mut Vec<GameEvent>) -> bool {
+        match self.phase {
+            MatchPhase::Lobby | MatchPhase::MatchOver => false,
+            MatchPhase::Playing => true,
+            MatchPhase::Countdown { ticks_left } => {
+                if ticks_left <= 1 {
+                    self.set_phase(MatchPhase::Playing, events);
+                } else {
+                    self.phase = MatchPhase::Countdown {
+                        ticks_left: ticks_left - 1,
+                    };
+                }
+                false
+            }
+            MatchPhase::RoundOver { ticks_left } => {
+                if ticks_left <= 1 {
+                    self.start_countdown(events);
+                } else {
+                    self.phase = MatchPhase::RoundOver {
+                        ticks_left: ticks_left - 1,
+                    };
+                }
+                false
+            }
+        }
+    }
+
+    /// Checks the win conditions at the end of a playing tick.
+    ///
+    /// The match is won once a team alone leads after reaching the score limit or
+    /// when time runs out. A tie at the time limit starts sudden-death overtime.
+    pub(super) fn update_match(&mut self, scored: bool, events: &mut Vec<GameEvent>) {
+        let score_limit_reached = self
+            .rules
+            .score_limit
+            .is_some_and(|limit| self.scores.iter().any(|&score| score >= limit));
+        let time_up = self.remaining_ticks() == Some(0);
+
+        if (score_limit_reached || time_up)
+            && let Some(winner) = self.leader()
+        {
+            self.set_phase(MatchPhase::MatchOver, events);
+            events.push(GameEvent::MatchOver {
+                winner,
+                scores: self.scores.clone(),
+                overtime: self.overtime,
+            });
+            return;
+        }
+
+        if time_up && !self.overtime {
+            self.overtime = true;
+            events.push(GameEvent::OvertimeStarted);
+        }
+        if scored {
+            match self.rules.ticks(self.rules.round_over_secs) {
+                0 => self.start_countdown(events),
+                ticks_left => self.set_phase(MatchPhase::RoundOver { ticks_left }, events),
+            }
+        }
+    }
+
+    /// The team with the highest score, unless several share it.
+    fn leader(&self) -> Option<usize> {
+        let best = self.scores.iter().max()?;
+        let mut leaders = self.scores.iter().positions(|score| score == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(team), None) => Some(team),
+            _ => None,
+        }
+    }
+
+    fn start_countdown(&mut self, events: &mut Vec<GameEvent>) {
+        match self.rules.ticks(self.rules.countdown_secs) {
+            0 => self.set_phase(MatchPhase::Playing, events),
+            ticks_left => self.set_phase(MatchPhase::Countdown { ticks_left }, events),
+        }
+    }
+
+    fn set_phase(&mut self, phase: MatchPhase, events: &mut Vec<GameEvent>) {
+        self.phase = phase;
+        events.push(GameEvent::PhaseChanged { phase });
+    }
+}
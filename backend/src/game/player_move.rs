@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Move {
     Up,
     UpRight,
@@ -30,6 +30,36 @@ impl Move {
         }
     }
 
+    /// The move as a numeric keypad digit, `8` being up and `5` standing still.
+    pub fn to_numpad(self) -> char {
+        match self {
+            Move::Up => '8',
+            Move::UpRight => '9',
+            Move::Right => '6',
+            Move::DownRight => '3',
+            Move::Down => '2',
+            Move::DownLeft => '1',
+            Move::Left => '4',
+            Move::UpLeft => '7',
+            Move::Stay => '5',
+        }
+    }
+
+    pub fn from_numpad(digit: char) -> Option<Self> {
+        match digit {
+            '8' => Some(Move::Up),
+            '9' => Some(Move::UpRight),
+            '6' => Some(Move::Right),
+            '3' => Some(Move::DownRight),
+            '2' => Some(Move::Down),
+            '1' => Some(Move::DownLeft),
+            '4' => Some(Move::Left),
+            '7' => Some(Move::UpLeft),
+            '5' => Some(Move::Stay),
+            _ => None,
+        }
+    }
+
     pub fn to_coords(self) -> (i32, i32) {
         match self {
             Move::Up => (0, -1),
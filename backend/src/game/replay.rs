@@ -0,0 +1,419 @@
+//! Match recordings.
+//!
+//! The simulation is deterministic, so a match is fully described by how it was
+//! set up and the moves made on every tick. A replay file is JSON Lines: a header
+//! with the map, team size and rules, then one line per input:
+//!
+//! ```text
+//! {"version":2,"map":"{\n  \"name\": \"Classic\", ...","team_size":2,"rules":{...}}
+//! {"type":"start"}
+//! {"type":"step","moves":"6545"}
+//! {"type":"step","moves":"6545"}
+//! {"type":"end","state_hash":"a1b2c3d4e5f60718"}
+//! ```
+//!
+//! `moves` holds one numeric keypad digit per player (see [`Move::to_numpad`]).
+//! The optional `end` line carries the [`GameState::state_hash`] the recording
+//! finished with, so playback can check it reached the same state.
+
+use super::{GameEvent, GameRules, GameState, MAX_TEAM_SIZE, Map, MapError, Move};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error as StdError,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+const REPLAY_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplayHeader {
+    version: u32,
+    map: String,
+    team_size: usize,
+    rules: GameRules,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ReplayEntry {
+    Start,
+    Step { moves: String },
+    End { state_hash: String },
+}
+
+#[derive(Debug, Clone)]
+enum ReplayInput {
+    Start,
+    Step(Vec<Move>),
+}
+
+/// Writes a match to a replay file as it is played.
+#[derive(Debug)]
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    /// Starts a recording of a game built with [`GameState::from_map`] and
+    /// [`GameState::with_rules`] from these arguments.
+    pub fn new(writer: W, map: &Map, team_size: usize, rules: &GameRules) -> io::Result<Self> {
+        let mut recorder = Self { writer };
+        recorder.write_line(&ReplayHeader {
+            version: REPLAY_VERSION,
+            map: map.to_string(),
+            team_size,
+            rules: rules.clone(),
+        })?;
+        Ok(recorder)
+    }
+
+    /// Records a call to [`GameState::start`].
+    pub fn record_start(&mut self) -> io::Result<()> {
+        self.write_line(&ReplayEntry::Start)
+    }
+
+    /// Records a call to [`GameState::step`] with `moves`.
+    pub fn record_step(&mut self, moves: &[Move]) -> io::Result<()> {
+        let moves = moves
+            .iter()
+            .map(|player_move| player_move.to_numpad())
+            .collect();
+        self.write_line(&ReplayEntry::Step { moves })
+    }
+
+    /// Ends the recording with the state `game` finished in.
+    pub fn finish(&mut self, game: &GameState) -> io::Result<()> {
+        self.write_line(&ReplayEntry::End {
+            state_hash: format!("{:016x}", game.state_hash()),
+        })?;
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+/// A recorded match being played back.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    initial: GameState,
+    game: GameState,
+    inputs: Vec<ReplayInput>,
+    // Inputs applied so far
+    position: usize,
+    // Steps applied so far
+    tick: usize,
+    tick_count: usize,
+    expected_hash: Option<u64>,
+}
+
+impl GameState {
+    /// Loads a replay file, ready to play back from the first tick.
+    pub fn replay(reader: impl BufRead) -> Result<Replay, ReplayError> {
+        let mut lines = reader.lines().enumerate();
+
+        let (_, header) = lines.next().ok_or(ReplayError::MissingHeader)?;
+        let header: ReplayHeader = serde_json::from_str(&header?)
+            .map_err(|source| ReplayError::InvalidLine { line: 1, source })?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(header.version));
+        }
+        if !(1..=MAX_TEAM_SIZE).contains(&header.team_size) {
+            return Err(ReplayError::InvalidTeamSize(header.team_size));
+        }
+        header.rules.validate().map_err(ReplayError::InvalidRules)?;
+        let map: Map = header.map.parse()?;
+        let game = GameState::from_map(&map, header.team_size)?.with_rules(header.rules);
+
+        let mut inputs = Vec::new();
+        let mut expected_hash = None;
+        for (index, line) in lines {
+            let line_number = index + 1;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|source| ReplayError::InvalidLine {
+                line: line_number,
+                source,
+            })?;
+            match entry {
+                ReplayEntry::Start => inputs.push(ReplayInput::Start),
+                ReplayEntry::Step { moves } => {
+                    let parsed: Option<Vec<Move>> = moves.chars().map(Move::from_numpad).collect();
+                    match parsed {
+                        Some(parsed) if parsed.len() == game.player_count() => {
+                            inputs.push(ReplayInput::Step(parsed))
+                        }
+                        _ => {
+                            return Err(ReplayError::InvalidMoves {
+                                line: line_number,
+                                moves,
+                            });
+                        }
+                    }
+                }
+                ReplayEntry::End { state_hash } => {
+                    let hash = u64::from_str_radix(&state_hash, 16).map_err(|_| {
+                        ReplayError::InvalidHash {
+                            line: line_number,
+                            state_hash,
+                        }
+                    })?;
+                    expected_hash = Some(hash);
+                }
+            }
+        }
+
+        let tick_count = inputs
+            .iter()
+            .filter(|input| matches!(input, ReplayInput::Step(_)))
+            .count();
+        Ok(Replay {
+            initial: game.clone(),
+            game,
+            inputs,
+            position: 0,
+            tick: 0,
+            tick_count,
+            expected_hash,
+        })
+    }
+}
+
+impl Replay {
+    /// The game as of the current tick.
+    pub fn game(&self) -> &GameState {
+        &self.game
+    }
+
+    /// Recorded ticks played back so far.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// Recorded ticks in the whole match.
+    pub fn tick_count(&self) -> usize {
+        self.tick_count
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.inputs.len()
+    }
+
+    /// The state hash the recording ended with, if it was finished properly.
+    pub fn expected_hash(&self) -> Option<u64> {
+        self.expected_hash
+    }
+
+    /// Whether playback has reached the end and ended in the recorded state.
+    /// `None` until the end, or if the recording has no final hash.
+    pub fn matches_recording(&self) -> Option<bool> {
+        let expected = self.expected_hash?;
+        self.is_finished()
+            .then(|| self.game.state_hash() == expected)
+    }
+
+    /// Plays the next recorded tick, returning its events, or `None` at the end.
+    pub fn step(&mut self) -> Option<Vec<GameEvent>> {
+        let mut events = Vec::new();
+        while let Some(input) = self.inputs.get(self.position) {
+            self.position += 1;
+            match input {
+                ReplayInput::Start => events.extend(self.game.start()),
+                ReplayInput::Step(moves) => {
+                    events.extend(self.game.step(moves));
+                    self.tick += 1;
+                    return Some(events);
+                }
+            }
+        }
+        (!events.is_empty()).then_some(events)
+    }
+
+    /// Jumps to just after recorded tick `tick`, replaying from the beginning
+    /// when going backwards.
+    pub fn seek(&mut self, tick: usize) {
+        if tick < self.tick {
+            self.game = self.initial.clone();
+            self.position = 0;
+            self.tick = 0;
+        }
+        while self.tick < tick && self.step().is_some() {}
+    }
+}
+
+/// Errors produced while loading a replay file.
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    MissingHeader,
+    UnsupportedVersion(u32),
+    InvalidLine {
+        line: usize,
+        source: serde_json::Error,
+    },
+    Map(MapError),
+    InvalidTeamSize(usize),
+    InvalidRules(String),
+    InvalidMoves {
+        line: usize,
+        moves: String,
+    },
+    InvalidHash {
+        line: usize,
+        state_hash: String,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not read replay: {err}"),
+            ReplayError::MissingHeader => write!(f, "replay is empty"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {version} is not supported, expected {REPLAY_VERSION}"
+            ),
+            ReplayError::InvalidLine { line, source } => write!(f, "line {line}: {source}"),
+            ReplayError::Map(err) => write!(f, "invalid replay map: {err}"),
+            ReplayError::InvalidTeamSize(team_size) => write!(
+                f,
+                "invalid replay team size {team_size}, expected 1 to {MAX_TEAM_SIZE}"
+            ),
+            ReplayError::InvalidRules(reason) => write!(f, "invalid replay rules: {reason}"),
+            ReplayError::InvalidMoves { line, moves } => {
+                write!(f, "line {line}: invalid moves {moves:?}")
+            }
+            ReplayError::InvalidHash { line, state_hash } => {
+                write!(f, "line {line}: invalid state hash {state_hash:?}")
+            }
+        }
+    }
+}
+
+impl StdError for ReplayError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ReplayError::Io(err) => Some(err),
+            ReplayError::InvalidLine { source, .. } => Some(source),
+            ReplayError::Map(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<MapError> for ReplayError {
+    fn from(err: MapError) -> Self {
+        ReplayError::Map(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::test_game::{test_rules, ticks};
+
+    fn rules() -> GameRules {
+        GameRules {
+            countdown_secs: ticks(2),
+            ..test_rules()
+        }
+    }
+
+    /// Records 40 ticks of player 0 running right, returning the file and the final game.
+    fn record() -> (Vec<u8>, GameState) {
+        let map = Map::classic();
+        let mut game = GameState::from_map(&map, 2).unwrap().with_rules(rules());
+        let mut recorder = ReplayWriter::new(Vec::new(), &map, 2, &rules()).unwrap();
+
+        game.start();
+        recorder.record_start().unwrap();
+        let moves = [Move::Right, Move::Stay, Move::Down, Move::Left];
+        for _ in 0..40 {
+            recorder.record_step(&moves).unwrap();
+            game.step(&moves);
+        }
+        recorder.finish(&game).unwrap();
+        (recorder.into_inner(), game)
+    }
+
+    #[test]
+    fn replays_a_recording_to_the_same_state() {
+        let (file, game) = record();
+        let text = String::from_utf8(file.clone()).unwrap();
+        assert!(text.lines().nth(2).unwrap().contains(r#""moves":"6524""#));
+
+        let mut replay = GameState::replay(file.as_slice()).unwrap();
+        assert_eq!(replay.tick_count(), 40);
+        assert_eq!(replay.matches_recording(), None);
+        while replay.step().is_some() {}
+
+        assert_eq!(replay.tick(), 40);
+        assert_eq!(replay.game().positions(), game.positions());
+        assert_eq!(replay.matches_recording(), Some(true));
+    }
+
+    #[test]
+    fn seeks_in_both_directions() {
+        let (file, _) = record();
+        let mut replay = GameState::replay(file.as_slice()).unwrap();
+        let mut straight = replay.clone();
+        for _ in 0..10 {
+            straight.step();
+        }
+
+        replay.seek(30);
+        assert_eq!(replay.tick(), 30);
+        replay.seek(10);
+        assert_eq!(replay.tick(), 10);
+        assert_eq!(replay.game().state_hash(), straight.game().state_hash());
+
+        replay.seek(1000);
+        assert_eq!(replay.tick(), 40);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn rejects_broken_recordings() {
+        let (file, _) = record();
+        let text = String::from_utf8(file).unwrap();
+
+        let bad_moves = text.replacen(r#""moves":"6524""#, r#""moves":"65""#, 1);
+        assert!(matches!(
+            GameState::replay(bad_moves.as_bytes()),
+            Err(ReplayError::InvalidMoves { line: 3, .. })
+        ));
+        let future = text.replacen(r#""version":2"#, r#""version":9"#, 1);
+        assert!(matches!(
+            GameState::replay(future.as_bytes()),
+            Err(ReplayError::UnsupportedVersion(9))
+        ));
+        let crowded = text.replacen(r#""team_size":2"#, r#""team_size":1000000000000"#, 1);
+        assert!(matches!(
+            GameState::replay(crowded.as_bytes()),
+            Err(ReplayError::InvalidTeamSize(1_000_000_000_000))
+        ));
+        assert!(matches!(
+            GameState::replay("".as_bytes()),
+            Err(ReplayError::MissingHeader)
+        ));
+    }
+}
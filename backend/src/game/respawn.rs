@@ -0,0 +1,75 @@
+use super::{GameEvent, GameState};
+
+impl GameState {
+    /// Whether `player_index` can move, tag, be tagged and touch flags.
+    /// Tagged players sit out at their spawn until their respawn timer runs out,
+    /// or in jail until a teammate rescues them.
+    pub fn is_in_play(&self, player_index: usize) -> bool {
+        self.respawn_ticks[player_index] == 0 && !self.jailed[player_index]
+    }
+
+    /// Whether `player_index` has just respawned and cannot be tagged yet.
+    pub fn is_protected(&self, player_index: usize) -> bool {
+        self.protection_ticks[player_index] > 0
+    }
+
+    /// Ticks each player still sits out before respawning, in player order.
+    pub fn get_respawn_ticks(&self) -> &[u32] {
+        &self.respawn_ticks
+    }
+
+    /// Ticks of spawn protection each player has left, in player order.
+    pub fn get_protection_ticks(&self) -> &[u32] {
+        &self.protection_ticks
+    }
+
+    /// Takes a tagged player out of play for the respawn delay.
+    pub(super) fn start_respawn(&mut self, player_index: usize) {
+        let respawn_delay = self.rules.ticks(self.rules.respawn_delay_secs);
+        self.respawn_ticks[player_index] = respawn_delay;
+        self.protection_ticks[player_index] = match respawn_delay {
+            0 => self.rules.ticks(self.rules.spawn_protection_secs),
+            _ => 0,
+        };
+    }
+
+    /// Whose respawn and protection timers are running as a tick starts, in player
+    /// order. Only these count the tick, so a timer set during a tick runs for
+    /// as many whole ticks after it as it was set to.
+    pub(super) fn running_timers(&self) -> Vec<(bool, bool)> {
+        self.respawn_ticks
+            .iter()
+            .zip(&self.protection_ticks)
+            .map(|(&respawn, &protection)| (respawn > 0, protection > 0))
+            .collect()
+    }
+
+    /// Counts down the timers that were `running` for the whole tick, bringing players
+    /// whose respawn timer ran out back into play with a fresh protection window.
+    pub(super) fn update_respawns(
+        &mut self,
+        running: &[(bool, bool)],
+        events: &mut Vec<GameEvent>,
+    ) {
+        for (player, &(respawning, protected)) in running.iter().enumerate() {
+            // A score may have cleared the timers since
+            if protected && self.protection_ticks[player] > 0 {
+                self.protection_ticks[player] -= 1;
+            }
+            if respawning && self.respawn_ticks[player] > 0 {
+                self.respawn_ticks[player] -= 1;
+                if self.respawn_ticks[player] == 0 {
+                    self.protection_ticks[player] =
+                        self.rules.ticks(self.rules.spawn_protection_secs);
+                    events.push(GameEvent::PlayerRespawned { player });
+                }
+            }
+        }
+    }
+
+    /// Clears every timer, as when all players are sent back for a new round.
+    pub(super) fn clear_respawns(&mut self) {
+        self.respawn_ticks.fill(0);
+        self.protection_ticks.fill(0);
+    }
+}
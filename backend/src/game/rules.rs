@@ -0,0 +1,213 @@
+use super::Fixed;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// House rules for a game. Every field has a default, so hosts only need to send
+/// the ones they want to change. Sizes and distances are in tiles and durations in
+/// seconds, both rounded to the nearest thousandth. Speeds and durations do not
+/// depend on `tick_rate`, so the game plays the same at any rate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameRules {
+    /// Simulation steps per second.
+    pub tick_rate: u32,
+    /// Tiles a player moves per second.
+    pub player_speed: Fixed,
+    /// Side length of a player's square hitbox.
+    pub player_size: Fixed,
+    /// Side length of a flag's square hitbox.
+    pub flag_size: Fixed,
+    /// Largest distance between player and flag centers that still touches the flag.
+    pub capture_distance: Fixed,
+    /// The match is won by the first team to reach this score.
+    pub score_limit: Option<usize>,
+    /// Seconds of play before the leading team wins. A tie goes to sudden-death overtime.
+    pub time_limit_secs: Option<Fixed>,
+    /// Seconds players wait at their spawns before each round.
+    pub countdown_secs: Fixed,
+    /// Seconds between a score and the next countdown.
+    pub round_over_secs: Fixed,
+    pub tag_behavior: TagBehavior,
+    /// Seconds a tagged player sits out of play at their spawn.
+    pub respawn_delay_secs: Fixed,
+    /// Seconds after respawning during which a player cannot be tagged.
+    pub spawn_protection_secs: Fixed,
+    /// Whether diagonal moves are allowed. When they are not, diagonal moves are ignored.
+    pub allow_diagonals: bool,
+    /// Seconds a dropped flag lies untouched before returning to its base.
+    pub flag_return_secs: Fixed,
+}
+
+/// What happens when a player is caught in enemy territory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TagBehavior {
+    /// The player is sent back to their spawn.
+    Respawn,
+    /// The player is locked in the tagging team's jail until a teammate touches it.
+    /// Needs a map with jails.
+    Jail,
+    /// Players cannot tag each other.
+    Off,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            tick_rate: 30,
+            player_speed: Fixed::from_raw(1250),
+            player_size: Fixed::ONE,
+            flag_size: Fixed::ONE,
+            capture_distance: Fixed::from_raw(1200), // Generous capture distance
+            score_limit: None,
+            time_limit_secs: None,
+            countdown_secs: Fixed::from_secs(3),
+            round_over_secs: Fixed::from_secs(2),
+            tag_behavior: TagBehavior::Respawn,
+            respawn_delay_secs: Fixed::from_secs(2),
+            spawn_protection_secs: Fixed::from_secs(2),
+            allow_diagonals: true,
+            flag_return_secs: Fixed::from_secs(10),
+        }
+    }
+}
+
+/// Highest supported `tick_rate`.
+const MAX_TICK_RATE: u32 = 120;
+
+impl GameRules {
+    /// Checks that the rules describe a playable game, returning the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_TICK_RATE).contains(&self.tick_rate) {
+            return Err(format!("tick_rate must be between 1 and {MAX_TICK_RATE}"));
+        }
+        // Moving further than a player is wide per tick would let players pass through walls
+        let step = self.player_step();
+        if !(step > Fixed::ZERO && step <= self.player_size) {
+            return Err(
+                "player_speed must be above 0 and at most player_size per tick".to_string(),
+            );
+        }
+        if !(self.player_size > Fixed::ZERO && self.player_size <= Fixed::ONE) {
+            return Err("player_size must be above 0 and at most 1".to_string());
+        }
+        if !(self.flag_size > Fixed::ZERO && self.flag_size <= Fixed::ONE) {
+            return Err("flag_size must be above 0 and at most 1".to_string());
+        }
+        if self.capture_distance < Fixed::ZERO {
+            return Err("capture_distance must not be negative".to_string());
+        }
+        if self.score_limit == Some(0) {
+            return Err("score_limit must be at least 1".to_string());
+        }
+        if self
+            .time_limit_secs
+            .is_some_and(|limit| limit <= Fixed::ZERO)
+        {
+            return Err("time_limit_secs must be above 0".to_string());
+        }
+        for (name, secs) in [
+            ("countdown_secs", self.countdown_secs),
+            ("round_over_secs", self.round_over_secs),
+            ("respawn_delay_secs", self.respawn_delay_secs),
+            ("spawn_protection_secs", self.spawn_protection_secs),
+            ("flag_return_secs", self.flag_return_secs),
+        ] {
+            if secs < Fixed::ZERO {
+                return Err(format!("{name} must not be negative"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tiles a player moves in one tick, to the nearest thousandth.
+    pub fn player_step(&self) -> Fixed {
+        let rate = i64::from(self.tick_rate.max(1));
+        let raw = (i64::from(self.player_speed.raw()) * 2 + rate) / (rate * 2);
+        Fixed::from_raw(raw as i32)
+    }
+
+    /// Whole ticks lasting at least `secs`.
+    pub fn ticks(&self, secs: Fixed) -> u32 {
+        let raw = secs.raw().max(0) as u64 * u64::from(self.tick_rate);
+        raw.div_ceil(Fixed::SCALE as u64) as u32
+    }
+
+    /// Real time between two ticks.
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.tick_rate.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_partial_rules_over_defaults() {
+        let rules: GameRules =
+            serde_json::from_str(r#"{ "score_limit": 3, "tag_behavior": "off" }"#).unwrap();
+        assert_eq!(
+            rules,
+            GameRules {
+                score_limit: Some(3),
+                tag_behavior: TagBehavior::Off,
+                ..GameRules::default()
+            }
+        );
+        assert!(serde_json::from_str::<GameRules>(r#"{ "speed": 1.0 }"#).is_err());
+    }
+
+    #[test]
+    fn rejects_unplayable_rules() {
+        assert_eq!(GameRules::default().validate(), Ok(()));
+        for rules in [
+            GameRules {
+                player_speed: Fixed::ZERO,
+                ..GameRules::default()
+            },
+            GameRules {
+                player_speed: Fixed::from_tiles(60),
+                ..GameRules::default()
+            },
+            GameRules {
+                tick_rate: 0,
+                ..GameRules::default()
+            },
+            GameRules {
+                countdown_secs: Fixed::from_secs(-1),
+                ..GameRules::default()
+            },
+            GameRules {
+                player_size: Fixed::ZERO,
+                ..GameRules::default()
+            },
+            GameRules {
+                score_limit: Some(0),
+                ..GameRules::default()
+            },
+        ] {
+            assert!(rules.validate().is_err(), "{rules:?}");
+        }
+    }
+
+    #[test]
+    fn converts_seconds_to_ticks_at_the_tick_rate() {
+        let rules = GameRules {
+            tick_rate: 30,
+            ..GameRules::default()
+        };
+        assert_eq!(rules.ticks(Fixed::from_secs(3)), 90);
+        assert_eq!(rules.ticks(Fixed::from_raw(10)), 1);
+        assert_eq!(rules.ticks(Fixed::ZERO), 0);
+        assert_eq!(rules.player_step(), Fixed::from_raw(42));
+
+        let slow = GameRules {
+            tick_rate: 5,
+            ..GameRules::default()
+        };
+        assert_eq!(slow.ticks(Fixed::from_secs(3)), 15);
+        assert_eq!(slow.player_step(), Fixed::from_raw(250));
+    }
+}
@@ -0,0 +1,399 @@
+use super::Map;
+use std::{collections::VecDeque, fmt};
+
+/// How bad a [`MapDiagnostic`] is. Maps with errors cannot be played, warnings
+/// are reported to the level designer but do not block loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The kinds of fairness symmetry a two-team map can have. Either one is enough
+/// for a map to be considered fair. Maps with more teams are not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Reflection across the vertical center line: `(x, y) -> (w - 1 - x, y)`.
+    Mirror,
+    /// Rotation by 180 degrees around the center: `(x, y) -> (w - 1 - x, h - 1 - y)`.
+    Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapEntity {
+    Wall { x: usize, y: usize },
+    Spawn { index: usize, x: usize, y: usize },
+    Flag { team: usize, x: usize, y: usize },
+    Jail { team: usize, x: usize, y: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapDiagnostic {
+    OutOfBounds(MapEntity),
+    InsideWall(MapEntity),
+    /// `team` has no spawn to put its players on.
+    NoSpawn {
+        team: usize,
+    },
+    /// The flag of `flag_team` cannot be walked to from spawn `spawn`.
+    UnreachableFlag {
+        flag_team: usize,
+        spawn: usize,
+    },
+    /// The jail of `jail_team` cannot be walked to from spawn `spawn`, so its
+    /// prisoners could never be rescued.
+    UnreachableJail {
+        jail_team: usize,
+        spawn: usize,
+    },
+    /// `entity` has no counterpart on the other team's half under `symmetry`.
+    Asymmetric {
+        symmetry: Symmetry,
+        entity: MapEntity,
+    },
+}
+
+impl MapDiagnostic {
+    pub fn severity(&self) -> Severity {
+        match self {
+            MapDiagnostic::Asymmetric { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for MapEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapEntity::Wall { x, y } => write!(f, "wall at ({x}, {y})"),
+            MapEntity::Spawn { index, x, y } => write!(f, "spawn {index} at ({x}, {y})"),
+            MapEntity::Flag { team, x, y } => write!(f, "team {team} flag at ({x}, {y})"),
+            MapEntity::Jail { team, x, y } => write!(f, "team {team} jail at ({x}, {y})"),
+        }
+    }
+}
+
+impl fmt::Display for MapDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapDiagnostic::OutOfBounds(entity) => write!(f, "{entity} is outside the map"),
+            MapDiagnostic::InsideWall(entity) => write!(f, "{entity} is inside a wall"),
+            MapDiagnostic::NoSpawn { team } => write!(f, "team {team} has no spawn"),
+            MapDiagnostic::UnreachableFlag { flag_team, spawn } => {
+                write!(
+                    f,
+                    "team {flag_team} flag cannot be reached from spawn {spawn}"
+                )
+            }
+            MapDiagnostic::UnreachableJail { jail_team, spawn } => {
+                write!(
+                    f,
+                    "team {jail_team} jail cannot be reached from spawn {spawn}"
+                )
+            }
+            MapDiagnostic::Asymmetric { symmetry, entity } => {
+                write!(f, "{entity} has no {symmetry:?}-symmetric counterpart")
+            }
+        }
+    }
+}
+
+impl Map {
+    /// Checks that the map is playable and fair.
+    ///
+    /// Reports spawns, flags and jails that are out of bounds or inside walls, teams
+    /// without spawns, flags and jails that cannot be reached from an enemy spawn,
+    /// and, if a two-team map is neither mirror nor point symmetric, the entities
+    /// breaking the symmetry closest to holding. Returns an empty list for a clean
+    /// map.
+    pub fn validate(&self) -> Vec<MapDiagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let walls = self.wall_grid();
+        for team in 0..self.teams().len() {
+            if self.team_spawns(team).is_empty() {
+                diagnostics.push(MapDiagnostic::NoSpawn { team });
+            }
+        }
+
+        let mut playable_spawns = Vec::new();
+        for (index, entity) in self.spawn_entities().enumerate() {
+            if self.check_placement(entity, &walls, &mut diagnostics) {
+                playable_spawns.push(index);
+            }
+        }
+        let mut playable_flags = Vec::new();
+        for (team, entity) in self.flag_entities().enumerate() {
+            if self.check_placement(entity, &walls, &mut diagnostics) {
+                playable_flags.push(team);
+            }
+        }
+        let mut playable_jails = Vec::new();
+        for (team, entity) in self.jail_entities().enumerate() {
+            if self.check_placement(entity, &walls, &mut diagnostics) {
+                playable_jails.push(team);
+            }
+        }
+
+        for spawn in playable_spawns {
+            let reachable = self.reachable_from(
+                self.player_spawn_x[spawn],
+                self.player_spawn_y[spawn],
+                &walls,
+            );
+            for &flag_team in &playable_flags {
+                if flag_team == self.player_spawn_team[spawn] {
+                    continue;
+                }
+                let (x, y) = (self.flag_spawn_x[flag_team], self.flag_spawn_y[flag_team]);
+                if !reachable[y][x] {
+                    diagnostics.push(MapDiagnostic::UnreachableFlag { flag_team, spawn });
+                }
+            }
+            for &jail_team in &playable_jails {
+                if jail_team == self.player_spawn_team[spawn] {
+                    continue;
+                }
+                let (x, y) = (self.jail_x[jail_team], self.jail_y[jail_team]);
+                if !reachable[y][x] {
+                    diagnostics.push(MapDiagnostic::UnreachableJail { jail_team, spawn });
+                }
+            }
+        }
+
+        if self.teams().len() != 2 {
+            return diagnostics;
+        }
+        let mirror = self.symmetry_violations(Symmetry::Mirror);
+        let point = self.symmetry_violations(Symmetry::Point);
+        if !mirror.is_empty() && !point.is_empty() {
+            let closest = if point.len() < mirror.len() {
+                point
+            } else {
+                mirror
+            };
+            diagnostics.extend(closest);
+        }
+
+        diagnostics
+    }
+
+    /// Returns whether `entity` sits on a free cell, recording a diagnostic otherwise.
+    fn check_placement(
+        &self,
+        entity: MapEntity,
+        walls: &[Vec<bool>],
+        diagnostics: &mut Vec<MapDiagnostic>,
+    ) -> bool {
+        let (x, y) = entity.position();
+        if x >= self.width || y >= self.height {
+            diagnostics.push(MapDiagnostic::OutOfBounds(entity));
+            false
+        } else if walls[y][x] {
+            diagnostics.push(MapDiagnostic::InsideWall(entity));
+            false
+        } else {
+            true
+        }
+    }
+
+    fn wall_grid(&self) -> Vec<Vec<bool>> {
+        let mut walls = vec![vec![false; self.width]; self.height];
+        for (x, y) in self.wall_x.iter().zip(self.wall_y.iter()) {
+            walls[*y][*x] = true;
+        }
+        walls
+    }
+
+    // Players are as big as a tile, so they can only squeeze through orthogonal gaps
+    fn reachable_from(&self, x: usize, y: usize, walls: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let mut reachable = vec![vec![false; self.width]; self.height];
+        let mut queue = VecDeque::from([(x, y)]);
+        reachable[y][x] = true;
+
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours {
+                if nx < self.width && ny < self.height && !walls[ny][nx] && !reachable[ny][nx] {
+                    reachable[ny][nx] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        reachable
+    }
+
+    fn symmetry_violations(&self, symmetry: Symmetry) -> Vec<MapDiagnostic> {
+        let reflect = |x: usize, y: usize| match symmetry {
+            Symmetry::Mirror => (self.width.wrapping_sub(x + 1), y),
+            Symmetry::Point => (
+                self.width.wrapping_sub(x + 1),
+                self.height.wrapping_sub(y + 1),
+            ),
+        };
+        let other_team = |team: usize| 1 - team;
+
+        let walls: Vec<MapEntity> = self
+            .wall_x
+            .iter()
+            .zip(self.wall_y.iter())
+            .map(|(&x, &y)| MapEntity::Wall { x, y })
+            .collect();
+        let spawns: Vec<MapEntity> = self.spawn_entities().collect();
+        let flags: Vec<MapEntity> = self.flag_entities().collect();
+        let jails: Vec<MapEntity> = self.jail_entities().collect();
+
+        let has_counterpart = |entity: &MapEntity| match *entity {
+            MapEntity::Wall { x, y } => {
+                let target = reflect(x, y);
+                walls.iter().any(|wall| wall.position() == target)
+            }
+            MapEntity::Spawn { index, x, y } => {
+                let target = (reflect(x, y), other_team(self.player_spawn_team[index]));
+                spawns.iter().any(|spawn| match *spawn {
+                    MapEntity::Spawn { index, x, y } => {
+                        ((x, y), self.player_spawn_team[index]) == target
+                    }
+                    _ => false,
+                })
+            }
+            MapEntity::Flag { team, x, y } => flags[other_team(team)].position() == reflect(x, y),
+            MapEntity::Jail { team, x, y } => jails[other_team(team)].position() == reflect(x, y),
+        };
+
+        walls
+            .iter()
+            .chain(spawns.iter())
+            .chain(flags.iter())
+            .chain(jails.iter())
+            .filter(|entity| !has_counterpart(entity))
+            .map(|&entity| MapDiagnostic::Asymmetric { symmetry, entity })
+            .collect()
+    }
+
+    fn spawn_entities(&self) -> impl Iterator<Item = MapEntity> + '_ {
+        self.player_spawn_x
+            .iter()
+            .zip(self.player_spawn_y.iter())
+            .enumerate()
+            .map(|(index, (&x, &y))| MapEntity::Spawn { index, x, y })
+    }
+
+    fn flag_entities(&self) -> impl Iterator<Item = MapEntity> + '_ {
+        self.flag_spawn_x
+            .iter()
+            .zip(self.flag_spawn_y.iter())
+            .enumerate()
+            .map(|(team, (&x, &y))| MapEntity::Flag { team, x, y })
+    }
+
+    fn jail_entities(&self) -> impl Iterator<Item = MapEntity> + '_ {
+        self.jail_x
+            .iter()
+            .zip(self.jail_y.iter())
+            .enumerate()
+            .map(|(team, (&x, &y))| MapEntity::Jail { team, x, y })
+    }
+}
+
+impl MapEntity {
+    fn position(&self) -> (usize, usize) {
+        match *self {
+            MapEntity::Wall { x, y }
+            | MapEntity::Spawn { x, y, .. }
+            | MapEntity::Flag { x, y, .. }
+            | MapEntity::Jail { x, y, .. } => (x, y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(spawns: &str, flags: &str, grid: &str) -> Map {
+        format!(
+            r#"{{ "name": "Test", "teams": ["Blue", "Red"], "spawns": [{spawns}], "flags": [{flags}] }}
+---
+{grid}"#
+        )
+        .parse()
+        .unwrap()
+    }
+
+    const SPAWNS: &str = r#"{ "team": 0, "x": 0, "y": 0 }, { "team": 1, "x": 5, "y": 0 }"#;
+    const FLAGS: &str = r#"{ "team": 0, "x": 0, "y": 2 }, { "team": 1, "x": 5, "y": 2 }"#;
+
+    #[test]
+    fn classic_map_is_clean() {
+        assert_eq!(Map::classic().validate(), vec![]);
+    }
+
+    #[test]
+    fn reports_bad_placements() {
+        let spawns = r#"{ "team": 0, "x": 1, "y": 1 }, { "team": 1, "x": 9, "y": 0 }"#;
+        let diagnostics = map(spawns, FLAGS, "......\n.#..#.\n......").validate();
+        assert!(
+            diagnostics.contains(&MapDiagnostic::InsideWall(MapEntity::Spawn {
+                index: 0,
+                x: 1,
+                y: 1
+            }))
+        );
+        assert!(
+            diagnostics.contains(&MapDiagnostic::OutOfBounds(MapEntity::Spawn {
+                index: 1,
+                x: 9,
+                y: 0
+            }))
+        );
+    }
+
+    #[test]
+    fn reports_unreachable_flags() {
+        let diagnostics = map(SPAWNS, FLAGS, "..##..\n..##..\n..##..").validate();
+        assert_eq!(
+            diagnostics,
+            vec![
+                MapDiagnostic::UnreachableFlag {
+                    flag_team: 1,
+                    spawn: 0
+                },
+                MapDiagnostic::UnreachableFlag {
+                    flag_team: 0,
+                    spawn: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_either_symmetry_and_reports_the_closest_one() {
+        assert_eq!(
+            map(SPAWNS, FLAGS, "......\n.#..#.\n......").validate(),
+            vec![]
+        );
+
+        let point_flags = r#"{ "team": 0, "x": 0, "y": 2 }, { "team": 1, "x": 5, "y": 0 }"#;
+        let point_spawns = r#"{ "team": 0, "x": 0, "y": 0 }, { "team": 1, "x": 5, "y": 2 }"#;
+        assert_eq!(
+            map(point_spawns, point_flags, ".#....\n......\n....#.").validate(),
+            vec![]
+        );
+
+        let diagnostics = map(SPAWNS, FLAGS, "......\n.#....\n......").validate();
+        assert_eq!(
+            diagnostics,
+            vec![MapDiagnostic::Asymmetric {
+                symmetry: Symmetry::Mirror,
+                entity: MapEntity::Wall { x: 1, y: 1 }
+            }]
+        );
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+    }
+}
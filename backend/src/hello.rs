@@ -26,5 +26,5 @@ async fn handler_hello(Query(params): Query<HelloParams>) -> impl IntoResponse {
 }
 
 async fn handler_good_bye() -> impl IntoResponse {
-    Html(format!("Good bye!"))
+    Html("Good bye!".to_string())
 }
@@ -3,8 +3,12 @@
 
 pub use self::error::{Error, Result};
 
+pub mod actor;
 pub mod error;
 pub mod game;
 pub mod hello;
+pub mod playback;
+pub mod protocol;
 pub mod room;
+pub mod snapshot;
 pub mod state;
@@ -7,7 +7,7 @@ use axum::{
     response::IntoResponse,
 };
 use ctf_backend::{hello::routes_hello, room::routes_room, state};
-use std::{borrow::Cow, sync::Arc, time::Duration};
+use std::{borrow::Cow, net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{net::TcpListener, signal};
 use tower::{BoxError, ServiceBuilder};
 use tower_http::cors::CorsLayer;
@@ -34,6 +34,7 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
     let shared_state = state::SharedState::default();
+    tokio::spawn(state::run_janitor(Arc::clone(&shared_state)));
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
     let app = Router::new()
         .layer(
@@ -59,10 +60,14 @@ async fn main() {
         let _ = shutdown_tx.send(true);
     };
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal)
-        .await
-        .unwrap();
+    // Bans in a room go by the client's address
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal)
+    .await
+    .unwrap();
 }
 
 async fn handle_error(error: BoxError) -> impl IntoResponse {
@@ -0,0 +1,174 @@
+//! Streams a room's recorded match to a `?role=replay` websocket.
+//!
+//! The recording is played back with the same `positions` and `game_events`
+//! messages as live play, at the default snapshot rate, so a client can render
+//! it the same way. The client controls playback with `pause`, `play`, `seek`
+//! (`{"type":"seek","tick":120}`) and `speed` (`{"type":"speed","speed":2.0}`)
+//! messages, and is told where playback is with `replay_state` messages.
+
+use crate::actor::{Room, is_snapshot_tick};
+use crate::game::{GameState, Replay};
+use crate::protocol::{Encoding, PlaybackCommand, Positions, ServerEvent};
+use crate::state::DEFAULT_SNAPSHOT_RATE;
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use serde_json;
+use std::time::Duration;
+use tokio::time::{self, Interval};
+use tracing::debug;
+
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 8.0;
+
+/// Plays the room's match as recorded when the socket connected, until the
+/// client leaves or the server shuts down.
+pub async fn play_replay(
+    mut socket: WebSocket,
+    room: &Room,
+    encoding: Encoding,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    let room_key = &room.key;
+    let history = match room.replay().await {
+        Ok(Some(history)) => history,
+        _ => {
+            let _ = send_error(&mut socket, "Room has no replay").await;
+            return;
+        }
+    };
+    let mut replay = match GameState::replay(history.as_slice()) {
+        Ok(replay) => replay,
+        Err(err) => {
+            debug!(room = %room_key, %err, "could not load replay");
+            let _ = send_error(&mut socket, &format!("Could not load replay: {err}")).await;
+            return;
+        }
+    };
+    debug!(room = %room_key, ticks = replay.tick_count(), "playing replay");
+
+    let tick_interval = replay.game().rules().tick_interval();
+    let tick_rate = replay.game().rules().tick_rate;
+    let mut paused = false;
+    let mut speed = 1.0;
+    let mut ticker = playback_ticker(tick_interval, speed);
+    // Events since the last frame, sent along with the next one
+    let mut pending_events = Vec::new();
+    if !send_frame(&mut socket, &replay, encoding, paused, speed).await {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if !paused && !replay.is_finished() => {
+                let Some(events) = replay.step() else { continue };
+                pending_events.extend(events);
+                if !is_snapshot_tick(replay.tick() as u64, tick_rate, DEFAULT_SNAPSHOT_RATE) && !replay.is_finished() {
+                    continue;
+                }
+                if !send_positions(&mut socket, &replay, encoding).await {
+                    break;
+                }
+                if !pending_events.is_empty() {
+                    let events = std::mem::take(&mut pending_events);
+                    if !send_event(&mut socket, &ServerEvent::GameEvents { events }).await {
+                        break;
+                    }
+                }
+                if replay.is_finished() && !send_event(&mut socket, &replay_state(&replay, paused, speed)).await {
+                    break;
+                }
+            }
+            result = socket.recv() => {
+                match result {
+                    Some(Ok(Message::Text(text))) => {
+                        let sent = match serde_json::from_str::<PlaybackCommand>(&text) {
+                            Ok(PlaybackCommand::Pause {}) => {
+                                paused = true;
+                                send_event(&mut socket, &replay_state(&replay, paused, speed)).await
+                            }
+                            Ok(PlaybackCommand::Play {}) => {
+                                paused = false;
+                                ticker.reset();
+                                send_event(&mut socket, &replay_state(&replay, paused, speed)).await
+                            }
+                            Ok(PlaybackCommand::Seek { tick }) => {
+                                replay.seek(tick.min(replay.tick_count()));
+                                pending_events.clear();
+                                ticker.reset();
+                                send_frame(&mut socket, &replay, encoding, paused, speed).await
+                            }
+                            Ok(PlaybackCommand::Speed { speed: new_speed }) => {
+                                if (MIN_SPEED..=MAX_SPEED).contains(&new_speed) {
+                                    speed = new_speed;
+                                    ticker = playback_ticker(tick_interval, speed);
+                                    send_event(&mut socket, &replay_state(&replay, paused, speed)).await
+                                } else {
+                                    send_error(&mut socket, &format!("speed must be between {MIN_SPEED} and {MAX_SPEED}")).await
+                                }
+                            }
+                            Err(_) => send_error(&mut socket, "Unknown replay command").await,
+                        };
+                        if !sent {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Ping(payload))) => { let _ = socket.send(Message::Pong(payload)).await; }
+                    Some(Ok(Message::Close(_))) => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) => break,
+                    None => break,
+                }
+            }
+            Ok(_) = shutdown_rx.changed() => {
+                let _ = socket.send(Message::Close(Some(CloseFrame { code: axum::extract::ws::close_code::NORMAL, reason: "server shutting down".into() }))).await;
+                break;
+            }
+        }
+    }
+    debug!(room = %room_key, "replay viewer left");
+}
+
+/// A ticker stepping the replay `speed` times as fast as live play.
+fn playback_ticker(tick_interval: Duration, speed: f64) -> Interval {
+    let period = tick_interval.div_f64(speed);
+    time::interval_at(time::Instant::now() + period, period)
+}
+
+fn replay_state(replay: &Replay, paused: bool, speed: f64) -> ServerEvent {
+    ServerEvent::ReplayState {
+        tick: replay.tick(),
+        tick_count: replay.tick_count(),
+        paused,
+        speed,
+        finished: replay.is_finished(),
+        matches_recording: replay.matches_recording(),
+    }
+}
+
+async fn send_positions(socket: &mut WebSocket, replay: &Replay, encoding: Encoding) -> bool {
+    let positions =
+        ServerEvent::Positions(Positions::from_game(replay.game(), replay.tick() as u32));
+    socket.send(positions.to_message(encoding)).await.is_ok()
+}
+
+/// Sends where playback is and the game as of that tick.
+async fn send_frame(
+    socket: &mut WebSocket,
+    replay: &Replay,
+    encoding: Encoding,
+    paused: bool,
+    speed: f64,
+) -> bool {
+    send_event(socket, &replay_state(replay, paused, speed)).await
+        && send_positions(socket, replay, encoding).await
+}
+
+async fn send_error(socket: &mut WebSocket, message: &str) -> bool {
+    let error = ServerEvent::Error {
+        message: message.to_string(),
+    };
+    send_event(socket, &error).await
+}
+
+async fn send_event(socket: &mut WebSocket, event: &ServerEvent) -> bool {
+    socket.send(Message::text(event.to_json())).await.is_ok()
+}
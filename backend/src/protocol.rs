@@ -0,0 +1,459 @@
+//! Every message sent over a room's websocket.
+//!
+//! Messages are JSON objects tagged by `type`. Clients send [`ClientEvent`]s, or
+//! [`PlaybackCommand`]s when watching a replay, and receive [`ServerEvent`]s.
+//! Clients may ask for a protocol version with `?protocol=<version>`, and the
+//! `welcome` message tells them which one the server speaks. Clients on slow
+//! connections can ask for `positions` frames as MessagePack with
+//! `?encoding=msgpack`; everything else is always JSON text.
+//!
+//! Every `positions` frame is numbered. A client that acknowledges frames with
+//! `{"type":"ack","snapshot":<n>}` is sent `positions_delta` frames holding only
+//! what changed since the last frame it acknowledged, and a full `positions`
+//! keyframe every [`KEYFRAME_INTERVAL`] frames. Clients that never acknowledge
+//! always get full frames.
+//!
+//! The protocol is published as a JSON Schema in
+//! `frontend/src/protocol/schema.json` for the frontend to generate its types
+//! from. A test keeps it up to date: run `UPDATE_PROTOCOL_SCHEMA=1 cargo test`
+//! after changing any message, then `npm run generate:protocol` in `frontend`.
+
+use crate::game::{FlagStatus, GameEvent, GameRules, GameState, MatchPhase};
+use axum::extract::ws::Message;
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings, json_schema};
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever a change to the messages could break an existing client.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every this many snapshots, all clients get a full `positions` frame.
+pub const KEYFRAME_INTERVAL: u32 = 20;
+
+/// What a connection may do in a room, chosen with the `role` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Host,
+    Player,
+    /// Receives everything broadcast to the room but cannot act in it.
+    Spectator,
+    /// Watches the room's recorded match instead of the live one.
+    Replay,
+}
+
+impl Role {
+    pub fn from_param(role: &str) -> Option<Role> {
+        match role {
+            "host" => Some(Role::Host),
+            "player" => Some(Role::Player),
+            "spectator" => Some(Role::Spectator),
+            "replay" => Some(Role::Replay),
+            _ => None,
+        }
+    }
+}
+
+/// How `positions` frames are sent, chosen with the `encoding` query parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Text messages holding JSON.
+    #[default]
+    Json,
+    /// Binary messages holding MessagePack, with the same field names as the JSON.
+    Msgpack,
+}
+
+impl Encoding {
+    pub fn from_param(encoding: &str) -> Option<Encoding> {
+        match encoding {
+            "json" => Some(Encoding::Json),
+            "msgpack" => Some(Encoding::Msgpack),
+            _ => None,
+        }
+    }
+}
+
+/// Who is in a room.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RoomInfo {
+    pub room_key: String,
+    pub players: usize,
+    pub max_players: usize,
+    pub spectators: usize,
+    /// Whether new players are kept out.
+    pub locked: bool,
+    /// Session id of the room's host, if it has one.
+    pub host: Option<String>,
+    /// Simulation steps per second.
+    pub tick_rate: u32,
+    /// `positions` frames sent per second.
+    pub snapshot_rate: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    /// Starts the match. Only the host may send it.
+    StartGame {},
+    /// Removes a player from the room. Host only.
+    KickPlayer {
+        player_id: i32,
+    },
+    /// Removes a player or spectator for good, so a player cannot resume. With
+    /// `by_address`, new connections from their address are refused too. Host only.
+    BanSession {
+        session_id: String,
+        #[serde(default)]
+        by_address: bool,
+    },
+    /// Keeps new players out while `locked`. Host only.
+    LockRoom {
+        locked: bool,
+    },
+    /// Goes back to the lobby with the same players. Host only.
+    RestartMatch {},
+    /// Makes a connected player the host. Host only.
+    TransferHost {
+        player_id: i32,
+    },
+    Chat {
+        content: String,
+    },
+    Move {
+        dx: i32,
+        dy: i32,
+        /// Numbers the client's moves, counting up. Numbered moves are applied one per
+        /// tick in order, and `input_seqs` in `positions` says which was applied last.
+        /// Moves without a number replace whatever the player was doing at once.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seq: Option<u32>,
+    },
+    /// The client has `positions` snapshot `snapshot`, so later frames can be
+    /// sent as deltas against it.
+    Ack {
+        snapshot: u32,
+    },
+}
+
+/// Controls replay playback on a `role=replay` connection.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaybackCommand {
+    Pause {},
+    Play {},
+    /// Jumps to just after recorded tick `tick`.
+    Seek {
+        tick: usize,
+    },
+    /// Plays `speed` times as fast as live play.
+    Speed {
+        speed: f64,
+    },
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Welcome {
+        protocol_version: u32,
+        role: Role,
+        encoding: Encoding,
+        session_id: String,
+        room: String,
+        room_info: Option<RoomInfo>,
+        /// Lets a player reclaim their slot with `?resume=<token>` after losing connection.
+        resume_token: Option<String>,
+    },
+    UserJoined {
+        session_id: String,
+        player_id: i32,
+    },
+    UserLeft {
+        session_id: String,
+    },
+    /// The player lost connection; their slot is held for them to resume.
+    UserDisconnected {
+        session_id: String,
+        player_id: i32,
+    },
+    UserRejoined {
+        session_id: String,
+        player_id: i32,
+    },
+    HostLeft {
+        session_id: String,
+    },
+    /// Session `session_id` is now the host, as player `player_id` if it plays.
+    HostChanged {
+        session_id: String,
+        player_id: Option<i32>,
+    },
+    /// The host removed a session from the room.
+    UserKicked {
+        session_id: String,
+        player_id: Option<i32>,
+        banned: bool,
+    },
+    RoomLocked {
+        locked: bool,
+    },
+    /// The host threw the match away. The room is back in the lobby.
+    MatchRestarted {
+        restarted_by: String,
+    },
+    SpectatorJoined {
+        session_id: String,
+        spectators: usize,
+    },
+    SpectatorLeft {
+        session_id: String,
+        spectators: usize,
+    },
+    /// The room's settings were changed before the match started.
+    RoomUpdated {
+        room_info: RoomInfo,
+        rules: GameRules,
+    },
+    GameStarted {
+        started_by: String,
+    },
+    Chat {
+        from: String,
+        content: String,
+    },
+    /// The game after every tick.
+    Positions(Positions),
+    /// What changed since snapshot `base`, which the client acknowledged.
+    PositionsDelta(PositionsDelta),
+    /// What happened during a tick, in order.
+    GameEvents {
+        events: Vec<GameEvent>,
+    },
+    MatchOver {
+        winner: usize,
+        winner_name: Option<String>,
+        scores: Vec<usize>,
+        overtime: bool,
+    },
+    /// Where replay playback is.
+    ReplayState {
+        tick: usize,
+        tick_count: usize,
+        paused: bool,
+        speed: f64,
+        finished: bool,
+        /// Whether playback ended in the recorded state, once it has finished.
+        matches_recording: Option<bool>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl ServerEvent {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("server events always serialize")
+    }
+
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        rmp_serde::to_vec_named(self).expect("server events always serialize")
+    }
+
+    /// The websocket message carrying this event in `encoding`. Only `positions`
+    /// and `positions_delta` frames are ever binary.
+    pub fn to_message(&self, encoding: Encoding) -> Message {
+        match (self, encoding) {
+            (ServerEvent::Positions(_) | ServerEvent::PositionsDelta(_), Encoding::Msgpack) => {
+                Message::binary(self.to_msgpack())
+            }
+            _ => Message::text(self.to_json()),
+        }
+    }
+}
+
+/// Everything clients need to draw the game. Per-player lists are in player order
+/// and per-team lists in team order.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Positions {
+    /// Counts up by one with every frame of the match.
+    pub snapshot: u32,
+    /// Top-left corner of each player, in tiles.
+    pub players: Vec<(f32, f32)>,
+    /// Who carries each team's flag.
+    pub flag_captors: Vec<Option<usize>>,
+    pub flags: Vec<FlagStatus>,
+    pub scores: Vec<usize>,
+    pub phase: MatchPhase,
+    pub overtime: bool,
+    pub remaining_ticks: Option<u32>,
+    pub respawn_ticks: Vec<u32>,
+    pub protection_ticks: Vec<u32>,
+    pub jailed: Vec<bool>,
+    /// Each team's jail cell, empty on maps without jails.
+    pub jails: Vec<(usize, usize)>,
+    /// The `seq` of the last `move` applied for each player, for clients predicting
+    /// their own movement. Left out of replays.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub input_seqs: Vec<Option<u32>>,
+}
+
+impl Positions {
+    pub fn from_game(game: &GameState, snapshot: u32) -> Self {
+        Positions {
+            snapshot,
+            players: game.positions(),
+            flag_captors: game.get_flag_captors().to_vec(),
+            flags: game.flags(),
+            scores: game.get_scores().to_vec(),
+            phase: game.phase(),
+            overtime: game.is_overtime(),
+            remaining_ticks: game.remaining_ticks(),
+            respawn_ticks: game.get_respawn_ticks().to_vec(),
+            protection_ticks: game.get_protection_ticks().to_vec(),
+            jailed: game.get_jailed().to_vec(),
+            jails: game.jails(),
+            input_seqs: Vec::new(),
+        }
+    }
+
+    pub fn with_input_seqs(mut self, input_seqs: Vec<Option<u32>>) -> Self {
+        self.input_seqs = input_seqs;
+        self
+    }
+}
+
+/// A [`Positions`] frame as the changes from an earlier one. Fields that did not
+/// change are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PositionsDelta {
+    pub snapshot: u32,
+    /// The snapshot the changes apply to.
+    pub base: u32,
+    /// Players that moved, as `[player, x, y]`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub players: Vec<(usize, f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag_captors: Option<Vec<Option<usize>>>,
+    /// Flags that moved or changed state.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<FlagStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scores: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<MatchPhase>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overtime: Option<bool>,
+    /// `null` once the match no longer has a time limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_ticks: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respawn_ticks: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protection_ticks: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jailed: Option<Vec<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_seqs: Option<Vec<Option<u32>>>,
+}
+
+impl PositionsDelta {
+    /// The changes that turn `base` into `current`.
+    pub fn between(base: &Positions, current: &Positions) -> Self {
+        fn changed<T: PartialEq + Clone>(base: &T, current: &T) -> Option<T> {
+            (base != current).then(|| current.clone())
+        }
+
+        PositionsDelta {
+            snapshot: current.snapshot,
+            base: base.snapshot,
+            players: current
+                .players
+                .iter()
+                .enumerate()
+                .filter(|&(player, position)| base.players.get(player) != Some(position))
+                .map(|(player, &(x, y))| (player, x, y))
+                .collect(),
+            flag_captors: changed(&base.flag_captors, &current.flag_captors),
+            flags: current
+                .flags
+                .iter()
+                .filter(|flag| !base.flags.contains(flag))
+                .copied()
+                .collect(),
+            scores: changed(&base.scores, &current.scores),
+            phase: changed(&base.phase, &current.phase),
+            overtime: changed(&base.overtime, &current.overtime),
+            remaining_ticks: changed(&base.remaining_ticks, &current.remaining_ticks),
+            respawn_ticks: changed(&base.respawn_ticks, &current.respawn_ticks),
+            protection_ticks: changed(&base.protection_ticks, &current.protection_ticks),
+            jailed: changed(&base.jailed, &current.jailed),
+            input_seqs: changed(&base.input_seqs, &current.input_seqs),
+        }
+    }
+}
+
+/// The JSON Schema of every message, with the client and server messages
+/// under `client`, `playback` and `server`.
+pub fn schema() -> Schema {
+    let mut generator = SchemaGenerator::new(SchemaSettings::draft2020_12());
+    let client = generator.subschema_for::<ClientEvent>();
+    let playback = generator.subschema_for::<PlaybackCommand>();
+    let server = generator.subschema_for::<ServerEvent>();
+    let definitions = generator.take_definitions(true);
+    json_schema!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Capture the Flag websocket protocol",
+        "version": PROTOCOL_VERSION,
+        "properties": {
+            "client": client,
+            "playback": playback,
+            "server": server,
+        },
+        "$defs": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::Path};
+
+    #[test]
+    fn msgpack_positions_hold_the_same_fields_as_json() {
+        let positions = ServerEvent::Positions(Positions::from_game(&GameState::new(), 0));
+        let Message::Binary(msgpack) = positions.to_message(Encoding::Msgpack) else {
+            panic!("positions should be binary in msgpack");
+        };
+        let decoded: serde_json::Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert_eq!(decoded, serde_json::to_value(&positions).unwrap());
+        assert!(msgpack.len() < positions.to_json().len());
+
+        let chat = ServerEvent::Chat {
+            from: "host".to_string(),
+            content: "hi".to_string(),
+        };
+        assert!(matches!(
+            chat.to_message(Encoding::Msgpack),
+            Message::Text(_)
+        ));
+    }
+
+    #[test]
+    fn published_schema_is_up_to_date() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../frontend/src/protocol/schema.json");
+        let schema = serde_json::to_string_pretty(&schema()).unwrap() + "\n";
+        if env::var_os("UPDATE_PROTOCOL_SCHEMA").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, schema).unwrap();
+            return;
+        }
+        let published = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            published == schema,
+            "{} is out of date, run `UPDATE_PROTOCOL_SCHEMA=1 cargo test` to regenerate it",
+            path.display()
+        );
+    }
+}
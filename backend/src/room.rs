@@ -1,191 +1,463 @@
-use crate::error::Error;
-use crate::game::Move as GameMove;
+use crate::actor::{HostAction, Outgoing, Room, RoomDetails, RoomSettings, RoomSummary};
+use crate::error::{Error, Result};
+use crate::game::{GameRules, Move as GameMove};
+use crate::playback::play_replay;
+use crate::protocol::{ClientEvent, Encoding, PROTOCOL_VERSION, Role, ServerEvent};
+use crate::snapshot::SnapshotEncoder;
 use crate::state::{
-    SharedState, add_player, add_ws_sender, broadcast_to_room, create_room, ensure_room_loop,
-    get_players_state, get_room_state, list_rooms, remove_player, update_player_state,
+    DEFAULT_SNAPSHOT_RATE, RESUME_GRACE, SharedState, create_room, delete_room, get_room,
+    list_rooms,
 };
 use axum::{
     Router,
     extract::{
-        Extension, Path, Query, State,
-        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Extension, Path, Query, State,
+        rejection::JsonRejection,
+        ws::{
+            CloseFrame, Message, WebSocket, WebSocketUpgrade, rejection::WebSocketUpgradeRejection,
+        },
     },
-    http::StatusCode,
-    response::{IntoResponse, Json},
-    routing::{get, post},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+    routing::get,
 };
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tokio::sync::broadcast::error::RecvError;
 use tracing::debug;
 use uuid::Uuid;
 
-type HttpResult<T> = std::result::Result<Json<T>, StatusCode>;
+const DEFAULT_TEAM_SIZE: usize = 2;
 
 pub fn routes_room() -> Router<SharedState> {
     Router::new()
-        .route("/rooms", post(handler_create_room))
-        .route("/rooms/{room_key}", get(ws_handler))
+        .route("/rooms", get(handler_list_rooms).post(handler_create_room))
+        .route(
+            "/rooms/{room_key}",
+            get(handler_get_room)
+                .patch(handler_update_room)
+                .delete(handler_delete_room),
+        )
+        .route("/rooms/{room_key}/replay", get(handler_get_replay))
+}
+
+/// Room settings sent to create or change a room. Any left out keep their
+/// default, or their current value when changing a room.
+#[derive(Deserialize)]
+struct RoomSettingsRequest {
+    team_size: Option<usize>,
+    rules: Option<GameRules>,
+    snapshot_rate: Option<u32>,
 }
 
 #[derive(Serialize)]
 struct CreateRoomResponse {
     room_key: String,
+    /// Connect with `?role=host&host_token=<token>` to host the room. Keep it secret.
+    host_token: String,
+    max_players: usize,
+    rules: GameRules,
+    snapshot_rate: u32,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-enum ClientEvent {
-    StartGame {},
-    Chat { content: String },
-    Move { dx: i32, dy: i32 },
+async fn handler_create_room(
+    State(state): State<SharedState>,
+    request: std::result::Result<Option<Json<RoomSettingsRequest>>, JsonRejection>,
+) -> Result<Json<CreateRoomResponse>> {
+    let (team_size, rules, snapshot_rate) = request?
+        .map(|Json(request)| (request.team_size, request.rules, request.snapshot_rate))
+        .unwrap_or_default();
+    let team_size = team_size.unwrap_or(DEFAULT_TEAM_SIZE);
+    let rules = rules.unwrap_or_default();
+    let snapshot_rate = snapshot_rate.unwrap_or(DEFAULT_SNAPSHOT_RATE);
+    debug!(
+        "Attempting to create a room with team_size={} rules={:?} snapshot_rate={}",
+        team_size, rules, snapshot_rate
+    );
+    let settings = RoomSettings {
+        team_size,
+        rules: rules.clone(),
+        snapshot_rate,
+    };
+    let room = create_room(&state, settings)?;
+    debug!("Created a room with room_key={}", room.key);
+    Ok(Json(CreateRoomResponse {
+        room_key: room.key.clone(),
+        host_token: room.host_token.clone(),
+        max_players: room.info().await?.max_players,
+        rules,
+        snapshot_rate,
+    }))
 }
 
 #[derive(Serialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-enum ServerEvent {
-    Welcome {
-        role: String,
-        session_id: String,
-        room: String,
-    },
-    UserJoined {
-        session_id: String,
-        player_id: i32,
-    },
-    UserLeft {
-        session_id: String,
-    },
-    HostLeft {
-        session_id: String,
-    },
-    GameStarted {
-        started_by: String,
-    },
-    Chat {
-        from: String,
-        content: String,
-    },
+struct ListRoomsResponse {
+    rooms: Vec<RoomSummary>,
+}
+
+async fn handler_list_rooms(State(state): State<SharedState>) -> Json<ListRoomsResponse> {
+    let mut rooms = Vec::new();
+    for room_key in list_rooms(&state) {
+        // Rooms closed since they were listed are left out
+        let Some(room) = get_room(&state, &room_key) else {
+            continue;
+        };
+        if let Ok(details) = room.details().await {
+            rooms.push(details.summary);
+        }
+    }
+    rooms.sort_by(|a, b| a.info.room_key.cmp(&b.info.room_key));
+    Json(ListRoomsResponse { rooms })
+}
+
+/// Checks the request carries the room's host token as `Authorization: Bearer <token>`.
+fn authorize_host(room: &Room, headers: &HeaderMap) -> Result<()> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if room.is_host_token(token) => Ok(()),
+        _ => Err(Error::Forbidden(
+            "the room's host token is required".to_string(),
+        )),
+    }
 }
 
-async fn handler_create_room(State(state): State<SharedState>) -> HttpResult<CreateRoomResponse> {
-    debug!("Attempting to create a room");
-    let room_key = create_room(&state);
-    debug!("Created a room with room_key={}", room_key);
-    Ok(Json(CreateRoomResponse { room_key }))
+/// Changes the room's settings. Only allowed before its match starts.
+async fn handler_update_room(
+    State(state): State<SharedState>,
+    Path(room_key): Path<String>,
+    headers: HeaderMap,
+    request: std::result::Result<Json<RoomSettingsRequest>, JsonRejection>,
+) -> Result<Json<RoomDetails>> {
+    let room = get_room(&state, &room_key).ok_or(Error::RoomNotFound)?;
+    authorize_host(&room, &headers)?;
+    let Json(request) = request?;
+    debug!(
+        "Attempting to update room_key={} with team_size={:?} rules={:?} snapshot_rate={:?}",
+        room_key, request.team_size, request.rules, request.snapshot_rate
+    );
+    let details = room
+        .configure(request.team_size, request.rules, request.snapshot_rate)
+        .await?;
+    Ok(Json(details))
 }
 
-async fn ws_handler(
+/// Deletes the room, hanging up on everyone connected to it.
+async fn handler_delete_room(
+    State(state): State<SharedState>,
+    Path(room_key): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    let room = get_room(&state, &room_key).ok_or(Error::RoomNotFound)?;
+    authorize_host(&room, &headers)?;
+    if !delete_room(&state, &room_key).await {
+        return Err(Error::RoomNotFound);
+    }
+    debug!("Deleted room_key={}", room_key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Downloads the room's match so far as a replay file.
+async fn handler_get_replay(
+    State(state): State<SharedState>,
+    Path(room_key): Path<String>,
+) -> Result<impl IntoResponse> {
+    let replay = get_room(&state, &room_key)
+        .ok_or(Error::RoomNotFound)?
+        .replay()
+        .await?
+        .ok_or(Error::NoReplay)?;
+    debug!(
+        "Sending replay of room_key={} ({} bytes)",
+        room_key,
+        replay.len()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{room_key}.jsonl\""),
+            ),
+        ],
+        replay,
+    ))
+}
+
+/// Connects to the room when asked to upgrade to a WebSocket, and otherwise
+/// describes it.
+async fn handler_get_room(
     Path(room_key): Path<String>,
     Query(params): Query<HashMap<String, String>>,
-    ws: WebSocketUpgrade,
+    ws: std::result::Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
     State(state): State<SharedState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     Extension(shutdown_rx): Extension<tokio::sync::watch::Receiver<bool>>,
-) -> impl IntoResponse {
+) -> Response {
+    let Ok(ws) = ws else {
+        return match get_room(&state, &room_key) {
+            Some(room) => room.details().await.map(Json).into_response(),
+            None => Error::RoomNotFound.into_response(),
+        };
+    };
     debug!(
         "ws_handler: incoming websocket upgrade for room room_key={} params={:?}",
         room_key, params
     );
-    if get_room_state(&state, &room_key).is_none() {
+    let Some(room) = get_room(&state, &room_key) else {
         debug!(
             "ws_handler: room {} not found, existing rooms: {:?}",
             room_key,
             list_rooms(&state)
         );
         return Error::RoomNotFound.into_response();
-    }
+    };
     ws.on_upgrade(move |socket| async move {
         let shutdown_rx = shutdown_rx.clone();
         debug!("ws_handler: upgrade successful for room {}", room_key);
-        handle_socket(socket, room_key, params, state, shutdown_rx).await;
+        handle_socket(socket, room, params, address.ip(), shutdown_rx).await;
     })
 }
 
 async fn handle_socket(
     mut socket: WebSocket,
-    room_key: String,
+    room: Arc<Room>,
     params: HashMap<String, String>,
-    state: SharedState,
+    address: IpAddr,
     mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
 ) {
-    let role = params
-        .get("role")
-        .map(|s| s.as_str())
-        .unwrap_or("player")
-        .to_string();
-    let session_id = Uuid::new_v4().to_string();
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
-    add_ws_sender(&state, &room_key, tx);
+    let role_param = params.get("role").map(|s| s.as_str()).unwrap_or("player");
+    let Some(role) = Role::from_param(role_param) else {
+        send_error(&mut socket, &format!("Unknown role: {role_param}")).await;
+        return;
+    };
+    if let Some(version) = params.get("protocol")
+        && version.parse() != Ok(PROTOCOL_VERSION)
+    {
+        let message = format!(
+            "Unsupported protocol version {version}, this server speaks version {PROTOCOL_VERSION}"
+        );
+        send_error(&mut socket, &message).await;
+        return;
+    }
+    let encoding_param = params.get("encoding").map(|s| s.as_str()).unwrap_or("json");
+    let Some(encoding) = Encoding::from_param(encoding_param) else {
+        send_error(&mut socket, &format!("Unknown encoding: {encoding_param}")).await;
+        return;
+    };
+
+    // Players take their slot before the welcome, so it can carry their resume token.
+    // `attached` is the disconnect count a resumed session came back with.
+    let (session_id, player_id, resume_token, attached) = match (role, params.get("resume")) {
+        (Role::Player, Some(token)) => match room.resume(token, address).await {
+            Ok(Some(session)) => (
+                session.session_id.clone(),
+                Some(session.player_id),
+                Some(token.clone()),
+                Some(session.disconnects()),
+            ),
+            Ok(None) => {
+                send_error(&mut socket, "Resume token is invalid or expired").await;
+                return;
+            }
+            Err(err) => {
+                send_error(&mut socket, &err.to_string()).await;
+                return;
+            }
+        },
+        (Role::Player, None) => {
+            let session_id = Uuid::new_v4().to_string();
+            match room.join(&session_id, address).await {
+                Ok(Some((player_id, token))) => (session_id, Some(player_id), Some(token), None),
+                Ok(None) => {
+                    // Room is full, close connection
+                    send_error(&mut socket, "Room is full").await;
+                    return;
+                }
+                Err(err) => {
+                    send_error(&mut socket, &err.to_string()).await;
+                    return;
+                }
+            }
+        }
+        (_, Some(_)) => {
+            send_error(&mut socket, "Only players can resume a session").await;
+            return;
+        }
+        (Role::Host, None) => {
+            let session_id = Uuid::new_v4().to_string();
+            let Some(host_token) = params.get("host_token") else {
+                send_error(&mut socket, "The host role needs the room's host_token").await;
+                return;
+            };
+            if let Err(err) = room.claim_host(&session_id, host_token).await {
+                send_error(&mut socket, &err.to_string()).await;
+                return;
+            }
+            (session_id, None, None, None)
+        }
+        (_, None) => (Uuid::new_v4().to_string(), None, None, None),
+    };
+    // Spectators are counted before the welcome too, which refuses banned ones
+    let spectators = if role == Role::Spectator {
+        match room.add_spectator(&session_id, address).await {
+            Ok(spectators) => Some(spectators),
+            Err(err) => {
+                send_error(&mut socket, &err.to_string()).await;
+                return;
+            }
+        }
+    } else {
+        None
+    };
 
     // Send structured welcome event
     let welcome_event = ServerEvent::Welcome {
-        role: role.clone(),
+        protocol_version: PROTOCOL_VERSION,
+        role,
+        encoding,
         session_id: session_id.clone(),
-        room: room_key.clone(),
+        room: room.key.clone(),
+        room_info: room.info().await.ok(),
+        resume_token: resume_token.clone(),
     };
     if socket
-        .send(Message::text(
-            serde_json::to_string(&welcome_event).unwrap(),
-        ))
+        .send(Message::text(welcome_event.to_json()))
         .await
         .is_err()
     {
+        if let (Some(player_id), Some(token)) = (player_id, resume_token) {
+            hold_slot(&room, token, attached.unwrap_or(0), player_id).await;
+        } else if role == Role::Host {
+            let _ = room.release_host(&session_id).await;
+        } else if role == Role::Spectator {
+            let _ = room.remove_spectator(&session_id).await;
+        }
         return;
     }
 
+    // Replay viewers watch the recording instead of the live room
+    if role == Role::Replay {
+        play_replay(socket, &room, encoding, shutdown_rx).await;
+        return;
+    }
+
+    let Ok(mut rx) = room.subscribe().await else {
+        send_error(&mut socket, "Room was closed").await;
+        return;
+    };
+    let mut snapshots = SnapshotEncoder::default();
+    // Set when the host removed this connection, which the room has already announced
+    let mut kicked = false;
+
     // Notify others that a player joined
-    let player_id = if role == "player" {
-        let player_id = add_player(&state, &room_key);
-        if player_id == -1 {
-            // Room is full, close connection
-            let _ = socket
-                .send(Message::text(
-                    r#"{"type":"error","message":"Room is full"}"#,
-                ))
-                .await;
-            return;
-        }
-        let joined = ServerEvent::UserJoined {
+    if let Some(player_id) = player_id {
+        let joined = if attached.is_some() {
+            ServerEvent::UserRejoined {
+                session_id: session_id.clone(),
+                player_id,
+            }
+        } else {
+            ServerEvent::UserJoined {
+                session_id: session_id.clone(),
+                player_id,
+            }
+        };
+        let _ = room.announce(joined).await;
+    }
+    if let Some(spectators) = spectators {
+        let joined = ServerEvent::SpectatorJoined {
             session_id: session_id.clone(),
-            player_id,
+            spectators,
         };
-
-        broadcast_to_room(&state, &room_key, &serde_json::to_string(&joined).unwrap());
-        player_id
-    } else {
-        0
-    };
+        let _ = room.announce(joined).await;
+    }
 
     loop {
         tokio::select! {
-            Some(msg) = rx.recv() => {
-                if socket.send(Message::text(msg)).await.is_err() { break; }
+            msg = rx.recv() => {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    // Numbered snapshots and acks let the client catch up on missed frames
+                    Err(RecvError::Lagged(missed)) => {
+                        debug!("Connection {} in room {} missed {} messages", session_id, room.key, missed);
+                        continue;
+                    }
+                    // The room was deleted
+                    Err(RecvError::Closed) => {
+                        let _ = socket.send(Message::Close(Some(CloseFrame { code: axum::extract::ws::close_code::AWAY, reason: "room closed".into() }))).await;
+                        break;
+                    }
+                };
+                let msg = match msg {
+                    Outgoing::Message(msg) => msg,
+                    Outgoing::Snapshot(positions) => snapshots.encode(positions).to_message(encoding),
+                    Outgoing::Disconnect { session_id: removed, reason } => {
+                        if removed != session_id { continue; }
+                        let _ = socket.send(Message::Close(Some(CloseFrame { code: axum::extract::ws::close_code::POLICY, reason: reason.into() }))).await;
+                        kicked = true;
+                        break;
+                    }
+                };
+                if socket.send(msg).await.is_err() { break; }
             }
             result = socket.recv() => {
                 match result {
                     Some(Ok(Message::Text(text))) => {
-                        match serde_json::from_str::<ClientEvent>(&text) {
-                            Ok(ClientEvent::StartGame {}) => {
-                                if role == "host" {
-                                    let game_started = ServerEvent::GameStarted { started_by: session_id.clone() };
-                                    broadcast_to_room(&state, &room_key, &serde_json::to_string(&game_started).unwrap());
-                                    ensure_room_loop(&state, &room_key);
-                                }
+                        let event = serde_json::from_str::<ClientEvent>(&text);
+                        if role == Role::Spectator && !matches!(event, Ok(ClientEvent::Ack { .. })) {
+                            // Spectators only watch, so anything but acknowledgements is refused
+                            let error = ServerEvent::Error { message: "Spectators cannot act in the room".to_string() };
+                            if socket.send(Message::text(error.to_json())).await.is_err() { break; }
+                            continue;
+                        }
+                        let host_action = match &event {
+                            Ok(ClientEvent::StartGame {}) => Some(HostAction::Start),
+                            Ok(ClientEvent::KickPlayer { player_id }) => Some(HostAction::Kick { player_id: *player_id }),
+                            Ok(ClientEvent::BanSession { session_id, by_address }) => Some(HostAction::Ban { session_id: session_id.clone(), by_address: *by_address }),
+                            Ok(ClientEvent::LockRoom { locked }) => Some(HostAction::Lock { locked: *locked }),
+                            Ok(ClientEvent::RestartMatch {}) => Some(HostAction::Restart),
+                            Ok(ClientEvent::TransferHost { player_id }) => Some(HostAction::Transfer { player_id: *player_id }),
+                            _ => None,
+                        };
+                        if let Some(action) = host_action {
+                            // The room checks this connection is its host
+                            if let Err(err) = room.host(&session_id, action).await {
+                                let error = ServerEvent::Error { message: err.to_string() };
+                                if socket.send(Message::text(error.to_json())).await.is_err() { break; }
                             }
+                            continue;
+                        }
+                        match event {
+                            Ok(ClientEvent::Ack { snapshot }) => snapshots.ack(snapshot),
                             Ok(ClientEvent::Chat { content }) => {
-                                let chat = ServerEvent::Chat { from: session_id.clone(), content };
-                                broadcast_to_room(&state, &room_key, &serde_json::to_string(&chat).unwrap());
+                                let _ = room.chat(&session_id, content).await;
                             }
-                            Ok(ClientEvent::Move {dx, dy}) => {
-                              let new_move = GameMove::new(dx, dy);
-                              update_player_state(&state, &room_key, player_id, new_move);
-                              let players_state = get_players_state(&state, &room_key);
-                              println!("Players state: {:?}", players_state);
+                            Ok(ClientEvent::Move {dx, dy, seq}) => {
+                              // Only players steer a game piece
+                              if let Some(player_id) = player_id {
+                                  let new_move = GameMove::new(dx, dy);
+                                  let _ = room.move_player(player_id, new_move, seq).await;
+                              }
                             }
+                            Ok(
+                                ClientEvent::StartGame {}
+                                | ClientEvent::KickPlayer { .. }
+                                | ClientEvent::BanSession { .. }
+                                | ClientEvent::LockRoom { .. }
+                                | ClientEvent::RestartMatch {}
+                                | ClientEvent::TransferHost { .. }
+                            ) => {}
                             Err(_) => {
                                 // Fallback: echo as chat
-                                let chat = ServerEvent::Chat { from: session_id.clone(), content: text.to_string() };
-                                broadcast_to_room(&state, &room_key, &serde_json::to_string(&chat).unwrap());
+                                let _ = room.chat(&session_id, text.to_string()).await;
                             }
                         }
                     }
@@ -204,18 +476,303 @@ async fn handle_socket(
         }
     }
 
-    // Connection is dropping; notify others based on role.
-    if role == "player" {
-        remove_player(&state, &room_key, player_id);
-        let left = ServerEvent::UserLeft {
-            session_id: session_id.clone(),
+    // Connection is dropping; stop listening first so the room knows whether it is
+    // empty, then notify others based on role.
+    drop(rx);
+    if kicked {
+        return;
+    }
+    let left = match (player_id, resume_token) {
+        (Some(player_id), Some(token)) => {
+            hold_slot(&room, token, attached.unwrap_or(0), player_id).await;
+            Some(ServerEvent::UserDisconnected {
+                session_id: session_id.clone(),
+                player_id,
+            })
+        }
+        _ => match role {
+            // The room tells everyone, and finds a new host
+            Role::Host => {
+                let _ = room.release_host(&session_id).await;
+                None
+            }
+            Role::Spectator => room
+                .remove_spectator(&session_id)
+                .await
+                .ok()
+                .map(|spectators| ServerEvent::SpectatorLeft {
+                    session_id: session_id.clone(),
+                    spectators,
+                }),
+            _ => None,
+        },
+    };
+    if let Some(left) = left {
+        let _ = room.announce(left).await;
+    }
+}
+
+/// Holds a disconnected player's slot, and gives it up if they do not resume in time.
+async fn hold_slot(room: &Arc<Room>, token: String, attached: u32, player_id: i32) {
+    let Ok(Some(disconnects)) = room.suspend(&token, attached).await else {
+        return;
+    };
+    let room = Arc::clone(room);
+    tokio::spawn(async move {
+        tokio::time::sleep(RESUME_GRACE).await;
+        if let Ok(Some(session)) = room.expire(&token, disconnects).await {
+            debug!("Player {} in room {} did not resume", player_id, room.key);
+            let left = ServerEvent::UserLeft {
+                session_id: session.session_id,
+            };
+            let _ = room.announce(left).await;
+        }
+    });
+}
+
+async fn send_error(socket: &mut WebSocket, message: &str) {
+    let error = ServerEvent::Error {
+        message: message.to_string(),
+    };
+    let _ = socket.send(Message::text(error.to_json())).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Fixed, GameState};
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{Value, json};
+    use std::net::Ipv4Addr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::watch;
+    use tokio::time::{self, Duration};
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite};
+    use tower::ServiceExt;
+
+    const ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn settings() -> RoomSettings {
+        RoomSettings {
+            team_size: 2,
+            rules: GameRules::default(),
+            snapshot_rate: DEFAULT_SNAPSHOT_RATE,
+        }
+    }
+
+    fn app(state: &SharedState) -> Router {
+        // The sender is dropped, so connections never hear of a shutdown
+        let (_, shutdown_rx) = watch::channel(false);
+        routes_room()
+            .with_state(Arc::clone(state))
+            .layer(Extension(shutdown_rx))
+    }
+
+    /// Serves the room routes on a free local port.
+    async fn serve(state: &SharedState) -> SocketAddr {
+        let listener = TcpListener::bind((ADDRESS, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = app(state).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        address
+    }
+
+    async fn connect(address: SocketAddr, path: &str) -> Client {
+        let (client, _) = connect_async(format!("ws://{address}{path}"))
+            .await
+            .unwrap();
+        client
+    }
+
+    async fn send(client: &mut Client, message: Value) {
+        let text = message.to_string();
+        client.send(tungstenite::Message::text(text)).await.unwrap();
+    }
+
+    /// Reads messages until one of type `kind` matches `wanted`, failing after a while.
+    async fn wait_for(client: &mut Client, kind: &str, wanted: impl Fn(&Value) -> bool) -> Value {
+        let find = async {
+            while let Some(message) = client.next().await {
+                if let tungstenite::Message::Text(text) = message.unwrap() {
+                    let message: Value = serde_json::from_str(text.as_str()).unwrap();
+                    if message["type"] == kind && wanted(&message) {
+                        return message;
+                    }
+                }
+            }
+            panic!("connection closed waiting for {kind}");
         };
-        broadcast_to_room(&state, &room_key, &serde_json::to_string(&left).unwrap());
-    } else if role == "host" {
-        let left = ServerEvent::HostLeft {
-            session_id: session_id.clone(),
+        time::timeout(Duration::from_secs(5), find)
+            .await
+            .unwrap_or_else(|_| panic!("no {kind} arrived"))
+    }
+
+    /// A room whose match has been playing for a few frames.
+    async fn recorded_room(state: &SharedState) -> Arc<Room> {
+        let settings = RoomSettings {
+            team_size: 1,
+            rules: GameRules {
+                countdown_secs: Fixed::ZERO,
+                ..GameRules::default()
+            },
+            snapshot_rate: DEFAULT_SNAPSHOT_RATE,
         };
-        broadcast_to_room(&state, &room_key, &serde_json::to_string(&left).unwrap());
+        let room = create_room(state, settings).unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        room.claim_host("host", &room.host_token).await.unwrap();
+        let (player_id, _) = room.join("first", ADDRESS).await.unwrap().unwrap();
+        room.move_player(player_id, GameMove::new(1, 0), None)
+            .await
+            .unwrap();
+        room.host("host", HostAction::Start).await.unwrap();
+        loop {
+            if let Ok(Outgoing::Snapshot(positions)) = events.recv().await
+                && positions.snapshot >= 5
+            {
+                return room;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_the_match_so_far() {
+        let state = SharedState::default();
+        let room = recorded_room(&state).await;
+
+        let request = Request::get(format!("/rooms/{}/replay", room.key))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(&state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let replay = GameState::replay(&body[..]).unwrap();
+        assert!(replay.tick_count() >= 10);
+        assert!(!replay.is_finished());
+    }
+
+    #[tokio::test]
+    async fn replay_viewers_pause_seek_and_change_speed() {
+        let state = SharedState::default();
+        let room = recorded_room(&state).await;
+        let address = serve(&state).await;
+        let mut client = connect(address, &format!("/rooms/{}?role=replay", room.key)).await;
+
+        let start = wait_for(&mut client, "replay_state", |_| true).await;
+        assert_eq!(start["tick"], 0);
+        assert!(start["tick_count"].as_u64().unwrap() >= 10);
+        wait_for(&mut client, "positions", |_| true).await;
+
+        send(&mut client, json!({"type": "pause"})).await;
+        wait_for(&mut client, "replay_state", |state| state["paused"] == true).await;
+
+        send(&mut client, json!({"type": "seek", "tick": 4})).await;
+        let seeked = wait_for(&mut client, "replay_state", |_| true).await;
+        assert_eq!(
+            (&seeked["tick"], &seeked["paused"]),
+            (&json!(4), &json!(true))
+        );
+        let positions = wait_for(&mut client, "positions", |_| true).await;
+        assert_eq!(positions["snapshot"], 4);
+
+        send(&mut client, json!({"type": "speed", "speed": 100})).await;
+        wait_for(&mut client, "error", |_| true).await;
+        send(&mut client, json!({"type": "speed", "speed": 4})).await;
+        wait_for(&mut client, "replay_state", |state| state["speed"] == 4.0).await;
+
+        // Playing on runs to the end of the recording
+        send(&mut client, json!({"type": "play"})).await;
+        wait_for(&mut client, "replay_state", |state| {
+            state["paused"] == false
+        })
+        .await;
+        let positions = wait_for(&mut client, "positions", |_| true).await;
+        assert!(positions["snapshot"].as_u64().unwrap() > 4);
+        let end = wait_for(&mut client, "replay_state", |state| {
+            state["finished"] == true
+        })
+        .await;
+        assert_eq!(end["tick"], end["tick_count"]);
+    }
+
+    #[tokio::test]
+    async fn spectators_watch_without_playing() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let address = serve(&state).await;
+        let path = format!("/rooms/{}", room.key);
+        let mut spectator = connect(address, &format!("{path}?role=spectator")).await;
+        let welcome = wait_for(&mut spectator, "welcome", |_| true).await;
+        assert_eq!(welcome["role"], "spectator");
+        assert!(welcome["resume_token"].is_null());
+
+        // Everything said in the room reaches them
+        let mut player = connect(address, &format!("{path}?role=player")).await;
+        let welcome = wait_for(&mut player, "welcome", |_| true).await;
+        let joined = wait_for(&mut spectator, "user_joined", |_| true).await;
+        assert_eq!(joined["session_id"], welcome["session_id"]);
+        send(&mut player, json!({"type": "chat", "content": "hi"})).await;
+        let chat = wait_for(&mut spectator, "chat", |_| true).await;
+        assert_eq!(chat["from"], welcome["session_id"]);
+
+        // but they cannot move or speak, and hold no slot
+        for event in [
+            json!({"type": "move", "dx": 1, "dy": 0}),
+            json!({"type": "chat", "content": "hello"}),
+            json!({"type": "start_game"}),
+        ] {
+            send(&mut spectator, event).await;
+            let error = wait_for(&mut spectator, "error", |_| true).await;
+            assert_eq!(error["message"], "Spectators cannot act in the room");
+        }
+        let info = room.info().await.unwrap();
+        assert_eq!((info.players, info.spectators), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn unknown_roles_are_refused() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let address = serve(&state).await;
+        let mut client = connect(address, &format!("/rooms/{}?role=admin", room.key)).await;
+
+        let error = wait_for(&mut client, "error", |_| true).await;
+        assert_eq!(error["message"], "Unknown role: admin");
+        let info = room.info().await.unwrap();
+        assert_eq!((info.players, info.spectators), (0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn holds_a_dropped_slot_until_the_grace_period_ends() {
+        let state = SharedState::default();
+        let settings = RoomSettings {
+            team_size: 1,
+            rules: GameRules::default(),
+            snapshot_rate: DEFAULT_SNAPSHOT_RATE,
+        };
+        let room = create_room(&state, settings).unwrap();
+        let (player_id, token) = room.join("first", ADDRESS).await.unwrap().unwrap();
+        let players = async || room.info().await.unwrap().players;
+
+        hold_slot(&room, token.clone(), 0, player_id).await;
+        time::sleep(RESUME_GRACE - Duration::from_secs(1)).await;
+        assert_eq!(players().await, 1);
+        let session = room.resume(&token, ADDRESS).await.unwrap().unwrap();
+        assert_eq!(session.player_id, player_id);
+        // The expiry for that disconnect finds the player back
+        time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(players().await, 1);
+
+        hold_slot(&room, token.clone(), session.disconnects(), player_id).await;
+        time::sleep(RESUME_GRACE + Duration::from_secs(1)).await;
+        assert_eq!(players().await, 0);
+        assert!(room.resume(&token, ADDRESS).await.unwrap().is_none());
     }
-    // Connection cleanup of dead senders happens lazily on next broadcast.
 }
@@ -0,0 +1,133 @@
+//! Delta encoding of `positions` frames for one connection.
+
+use crate::protocol::{KEYFRAME_INTERVAL, Positions, PositionsDelta, ServerEvent};
+use std::{collections::VecDeque, sync::Arc};
+
+/// Frames kept to diff against. Acknowledgements of older frames are ignored.
+const SENT_HISTORY: usize = 32;
+
+/// The frames recently sent to a connection and the newest one it acknowledged.
+#[derive(Debug, Default)]
+pub struct SnapshotEncoder {
+    sent: VecDeque<Arc<Positions>>,
+    acked: Option<u32>,
+}
+
+impl SnapshotEncoder {
+    pub fn ack(&mut self, snapshot: u32) {
+        self.acked = self.acked.max(Some(snapshot));
+    }
+
+    /// The frame to send for `positions`: a delta against the last acknowledged
+    /// frame if there is one, or the full frame on keyframes and otherwise.
+    pub fn encode(&mut self, positions: Arc<Positions>) -> ServerEvent {
+        // Numbering starts over with a new match
+        if self
+            .sent
+            .back()
+            .is_some_and(|last| last.snapshot >= positions.snapshot)
+        {
+            self.sent.clear();
+            self.acked = None;
+        }
+
+        let is_keyframe = positions.snapshot.is_multiple_of(KEYFRAME_INTERVAL);
+        let base = self
+            .acked
+            .filter(|_| !is_keyframe)
+            .and_then(|acked| self.sent.iter().find(|sent| sent.snapshot == acked));
+        let event = match base {
+            Some(base) => ServerEvent::PositionsDelta(PositionsDelta::between(base, &positions)),
+            None => ServerEvent::Positions(Positions::clone(&positions)),
+        };
+
+        self.sent.push_back(positions);
+        if self.sent.len() > SENT_HISTORY {
+            self.sent.pop_front();
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Fixed, GameRules, GameState, Move};
+
+    fn frames(count: u32) -> Vec<Arc<Positions>> {
+        let mut game = GameState::new().with_rules(GameRules {
+            countdown_secs: Fixed::ZERO,
+            ..GameRules::default()
+        });
+        game.start();
+        let moves = vec![Move::new(1, 0); game.player_count()];
+        (1..=count)
+            .map(|snapshot| {
+                game.step(&moves);
+                Arc::new(Positions::from_game(&game, snapshot))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sends_deltas_against_the_acknowledged_frame() {
+        let frames = frames(3);
+        let mut encoder = SnapshotEncoder::default();
+
+        assert!(matches!(
+            encoder.encode(frames[0].clone()),
+            ServerEvent::Positions(_)
+        ));
+        // Not acknowledged yet, so the client may not have anything to apply a delta to
+        assert!(matches!(
+            encoder.encode(frames[1].clone()),
+            ServerEvent::Positions(_)
+        ));
+
+        encoder.ack(1);
+        let ServerEvent::PositionsDelta(delta) = encoder.encode(frames[2].clone()) else {
+            panic!("expected a delta once a frame was acknowledged");
+        };
+        assert_eq!(delta, PositionsDelta::between(&frames[0], &frames[2]));
+        assert_eq!((delta.base, delta.snapshot), (1, 3));
+        assert!(!delta.players.is_empty());
+        assert_eq!(delta.scores, None);
+        assert_eq!(delta.jailed, None);
+    }
+
+    #[test]
+    fn deltas_tell_when_the_time_limit_goes_away() {
+        let frames = frames(2);
+        let mut timed = Positions::clone(&frames[0]);
+        timed.remaining_ticks = Some(90);
+        let untimed = Positions::clone(&frames[1]);
+
+        let delta = serde_json::to_value(PositionsDelta::between(&timed, &untimed)).unwrap();
+        assert_eq!(delta.get("remaining_ticks"), Some(&serde_json::Value::Null));
+        let delta = serde_json::to_value(PositionsDelta::between(&frames[0], &untimed)).unwrap();
+        assert_eq!(delta.get("remaining_ticks"), None);
+    }
+
+    #[test]
+    fn keyframes_and_unknown_acks_send_full_frames() {
+        let frames = frames(KEYFRAME_INTERVAL);
+        let mut encoder = SnapshotEncoder::default();
+        for frame in &frames[..frames.len() - 1] {
+            encoder.encode(frame.clone());
+        }
+
+        encoder.ack(KEYFRAME_INTERVAL - 1);
+        assert!(matches!(
+            encoder.encode(frames[frames.len() - 1].clone()),
+            ServerEvent::Positions(_)
+        ));
+
+        let mut encoder = SnapshotEncoder::default();
+        encoder.encode(frames[0].clone());
+        encoder.ack(7);
+        assert!(matches!(
+            encoder.encode(frames[1].clone()),
+            ServerEvent::Positions(_)
+        ));
+    }
+}
@@ -1,266 +1,447 @@
-use crate::game::{GameState, Move};
+use crate::actor::{Room, RoomSettings};
+use crate::error::Result;
+use crate::game::Map;
 use rand::Rng;
-use serde_json;
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
 };
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::task::JoinHandle;
 use tokio::time::{self, Duration};
+use tracing::{debug, warn};
 
-pub type SharedState = Arc<RwLock<AppState>>;
+pub type SharedState = Arc<AppState>;
 
-type RoomGame = GameState<4>; // adjust N as needed (2 or 4 supported by GameState)
+/// `positions` frames sent per second when the room does not choose a rate.
+pub const DEFAULT_SNAPSHOT_RATE: u32 = 15;
 
+/// How long a disconnected player's slot is held for them to resume it.
+pub const RESUME_GRACE: Duration = Duration::from_secs(30);
+
+/// Environment variable setting how many seconds a room may sit with nobody
+/// connected before it is deleted.
+const ROOM_IDLE_SECS_VAR: &str = "CTF_ROOM_IDLE_SECS";
+
+const DEFAULT_ROOM_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time between two sweeps of the janitor.
+const JANITOR_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Every open room, by room key. Each room runs as its own task, so this lock is only
+/// held to find, add or remove a room.
 #[derive(Default, Debug)]
 pub struct AppState {
-    pub room_senders: HashMap<String, Vec<UnboundedSender<String>>>,
-    pub room_state: HashMap<String, HashMap<i32, Move>>,
-    pub room_game: HashMap<String, RoomGame>,
-    pub room_players: HashMap<String, Vec<i32>>, // stable player-id -> index order
-    pub room_tasks: HashMap<String, JoinHandle<()>>, // running tick loops per room
+    rooms: RwLock<HashMap<String, Arc<Room>>>,
 }
 
-/// Creates a new room with the given room_key if it does not exist, returning its unique ID or an error.
-pub fn create_room(state: &SharedState) -> String {
-    let mut guard = state.write().unwrap();
+/// Creates a new room set up with `settings` under a unique room key, or returns an error.
+pub fn create_room(state: &SharedState, settings: RoomSettings) -> Result<Arc<Room>> {
+    let map = Map::classic();
+    let game = settings.new_game(&map)?;
+
+    // The room is only added once it is running, so no one can see it half-built
+    let mut rooms = state.rooms.write().unwrap();
     let mut rng = rand::rng();
-    let room_key;
-    loop {
+    let room_key = loop {
         let candidate = format!("{:06}", rng.random_range(0..1_000_000));
-        if guard.room_state.contains_key(&candidate) {
-            continue;
-        } else {
-            room_key = candidate;
-            guard
-                .room_state
-                .insert(room_key.to_string(), HashMap::new());
-            guard.room_players.insert(room_key.to_string(), Vec::new());
-            guard
-                .room_game
-                .insert(room_key.to_string(), RoomGame::new());
-            break;
+        if !rooms.contains_key(&candidate) {
+            break candidate;
         }
-    }
-    room_key
+    };
+    let room = Room::spawn(room_key.clone(), map, settings, game);
+    rooms.insert(room_key, Arc::clone(&room));
+    Ok(room)
 }
 
-/// Retrieves the ID of the room with the given room_key, if it exists.
-pub fn get_room_state(state: &SharedState, room_key: &str) -> Option<HashMap<i32, Move>> {
-    let guard = state.read().unwrap();
-    guard.room_state.get(room_key).cloned()
+/// Finds the room with the given room_key.
+pub fn get_room(state: &SharedState, room_key: &str) -> Option<Arc<Room>> {
+    state.rooms.read().unwrap().get(room_key).cloned()
 }
 
-/// Deletes the room with the given room_key, returning true if removed.
-pub fn delete_room(state: &SharedState, room_key: &str) -> bool {
-    let mut guard = state.write().unwrap();
-    guard.room_state.remove(room_key).is_some()
+/// Deletes the room with the given room_key, stopping it and closing its connections.
+/// Returns true if removed.
+pub async fn delete_room(state: &SharedState, room_key: &str) -> bool {
+    let Some(room) = state.rooms.write().unwrap().remove(room_key) else {
+        return false;
+    };
+    room.close().await;
+    true
 }
 
-/// Lists all rooms as (room_key, ID) pairs.
+/// Lists the keys of all rooms.
 pub fn list_rooms(state: &SharedState) -> Vec<String> {
-    let guard = state.read().unwrap();
-    guard.room_state.keys().cloned().collect()
+    state.rooms.read().unwrap().keys().cloned().collect()
 }
 
-/// Register a websocket sender for a room so we can broadcast to it later.
-pub fn add_ws_sender(state: &SharedState, room_key: &str, sender: UnboundedSender<String>) {
-    let mut guard = state.write().unwrap();
-    guard
-        .room_senders
-        .entry(room_key.to_string())
-        .or_default()
-        .push(sender);
+/// What one sweep of the janitor cleaned up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Reaped {
+    pub rooms: usize,
+    /// Room tasks that were still running and had to be stopped.
+    pub tasks: usize,
 }
 
-pub fn add_player(state: &SharedState, room_key: &str) -> i32 {
-    let mut guard = state.write().unwrap();
-
-    // Find first available slot from 0-3
-    let mut available_id = None;
-    let order = guard.room_players.entry(room_key.to_string()).or_default();
-    for id in 0..4 {
-        if !order.contains(&id) {
-            available_id = Some(id);
-            break;
+/// Deletes every room nobody has been connected to for `idle_timeout`, and every
+/// room whose task has died.
+pub async fn reap_idle_rooms(state: &SharedState, idle_timeout: Duration) -> Reaped {
+    let rooms: Vec<Arc<Room>> = state.rooms.read().unwrap().values().cloned().collect();
+    let mut reaped = Reaped::default();
+    for room in rooms {
+        let expired = match room.idle_for().await {
+            Ok(idle_for) => idle_for.is_some_and(|idle_for| idle_for >= idle_timeout),
+            Err(_) => true,
+        };
+        // Someone else may have deleted it in the meantime
+        if !expired || state.rooms.write().unwrap().remove(&room.key).is_none() {
+            continue;
+        }
+        reaped.rooms += 1;
+        if room.close().await {
+            reaped.tasks += 1;
         }
     }
+    reaped
+}
 
-    match available_id {
-        Some(id) => {
-            // Add to room state
-            let room = guard.room_state.entry(room_key.to_string()).or_default();
-            room.insert(id, Move::Stay);
-
-            // Add to player order
-            let order = guard.room_players.entry(room_key.to_string()).or_default();
-            order.push(id);
-
-            id
-        }
+/// The idle timeout from `CTF_ROOM_IDLE_SECS`, or the default if it is unset or invalid.
+fn room_idle_timeout() -> Duration {
+    let Some(secs) = std::env::var_os(ROOM_IDLE_SECS_VAR) else {
+        return DEFAULT_ROOM_IDLE_TIMEOUT;
+    };
+    match secs.to_str().and_then(|secs| secs.parse().ok()) {
+        Some(secs) => Duration::from_secs(secs),
         None => {
-            // Room is full, return -1 to indicate failure
-            -1
+            warn!(?secs, "invalid {}, using the default", ROOM_IDLE_SECS_VAR);
+            DEFAULT_ROOM_IDLE_TIMEOUT
         }
     }
 }
 
-pub fn update_player_state(state: &SharedState, room_key: &str, player_id: i32, new_move: Move) {
-    let mut guard = state.write().unwrap();
-    guard
-        .room_state
-        .entry(room_key.to_string())
-        .or_default()
-        .insert(player_id, new_move);
-}
-
-pub fn get_players_state(state: &SharedState, room_key: &str) -> Option<HashMap<i32, Move>> {
-    let guard = state.read().unwrap();
-    guard.room_state.get(room_key).cloned()
+/// Sweeps idle rooms away for as long as the server runs.
+pub async fn run_janitor(state: SharedState) {
+    let idle_timeout = room_idle_timeout();
+    debug!(?idle_timeout, "janitor started");
+    let mut sweeps = time::interval(JANITOR_INTERVAL);
+    loop {
+        sweeps.tick().await;
+        let reaped = reap_idle_rooms(&state, idle_timeout).await;
+        if reaped != Reaped::default() {
+            debug!(
+                rooms = reaped.rooms,
+                tasks = reaped.tasks,
+                remaining = list_rooms(&state).len(),
+                "janitor reaped idle rooms"
+            );
+        }
+    }
 }
 
-pub fn remove_player(state: &SharedState, room_key: &str, player_id: i32) {
-    let mut guard = state.write().unwrap();
-
-    // Remove from room_state
-    if let Some(room) = guard.room_state.get_mut(room_key) {
-        room.remove(&player_id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::{HostAction, Outgoing};
+    use crate::error::Error;
+    use crate::game::{Fixed, GameRules, GameState, MatchPhase, Move};
+    use axum::extract::ws::Message;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tokio::sync::broadcast::{self, error::RecvError};
+
+    const ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn settings() -> RoomSettings {
+        RoomSettings {
+            team_size: 2,
+            rules: GameRules::default(),
+            snapshot_rate: DEFAULT_SNAPSHOT_RATE,
+        }
     }
 
-    // Remove from room_players order
-    if let Some(order) = guard.room_players.get_mut(room_key) {
-        order.retain(|&id| id != player_id);
+    #[tokio::test]
+    async fn deleting_a_room_removes_all_of_it() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        assert!(room.join("session", ADDRESS).await.unwrap().is_some());
+        assert_eq!(list_rooms(&state), vec![room.key.clone()]);
+
+        assert!(delete_room(&state, &room.key).await);
+        assert!(get_room(&state, &room.key).is_none());
+        assert!(list_rooms(&state).is_empty());
+        // Its connections are told to hang up
+        assert!(matches!(events.recv().await, Err(RecvError::Closed)));
+        assert!(room.info().await.is_err());
+        assert!(!delete_room(&state, &room.key).await);
     }
-}
 
-/// Broadcast a text message to all active senders in the room, pruning dead ones.
-pub fn broadcast_to_room(state: &SharedState, room_key: &str, msg: &str) {
-    let mut guard = state.write().unwrap();
-    if let Some(senders) = guard.room_senders.get_mut(room_key) {
-        senders.retain(|tx| tx.send(msg.to_string()).is_ok());
+    #[tokio::test]
+    async fn reaps_only_rooms_nobody_is_connected_to() {
+        let state = SharedState::default();
+        let empty = create_room(&state, settings()).unwrap();
+        let watched = create_room(&state, settings()).unwrap();
+        let _events = watched.subscribe().await.unwrap();
+
+        let reaped = reap_idle_rooms(&state, Duration::from_secs(60)).await;
+        assert_eq!(reaped, Reaped::default());
+
+        let reaped = reap_idle_rooms(&state, Duration::ZERO).await;
+        assert_eq!(reaped, Reaped { rooms: 1, tasks: 1 });
+        assert!(get_room(&state, &empty.key).is_none());
+        assert!(get_room(&state, &watched.key).is_some());
+        assert_eq!(watched.idle_for().await.unwrap(), None);
     }
-}
 
-pub fn ensure_room_loop(state: &SharedState, room_key: &str) {
-    let mut guard = state.write().unwrap();
+    #[tokio::test]
+    async fn rooms_can_be_set_up_again_until_the_match_starts() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        assert!(room.join("first", ADDRESS).await.unwrap().is_some());
+        assert!(room.join("second", ADDRESS).await.unwrap().is_some());
+
+        let rules = GameRules {
+            tick_rate: 20,
+            ..GameRules::default()
+        };
+        let details = room
+            .configure(Some(3), Some(rules), Some(10))
+            .await
+            .unwrap();
+        assert_eq!(details.team_size, 3);
+        assert_eq!(details.summary.info.max_players, 6);
+        assert_eq!(details.summary.info.players, 2);
+        assert_eq!(details.summary.info.tick_rate, 20);
+        assert_eq!(details.summary.info.snapshot_rate, 10);
+        let Ok(Outgoing::Message(Message::Text(update))) = events.recv().await else {
+            panic!("expected the update to be broadcast");
+        };
+        assert!(update.contains("room_updated"));
+
+        // Settings left out are kept, and bad ones change nothing
+        let details = room.configure(None, None, Some(20)).await.unwrap();
+        assert_eq!(details.team_size, 3);
+        assert!(matches!(
+            room.configure(Some(1_000_000_000_000), None, None).await,
+            Err(Error::InvalidRoomConfig(_))
+        ));
+        assert!(matches!(
+            room.configure(None, None, Some(21)).await,
+            Err(Error::InvalidRoomConfig(_))
+        ));
+        // A 1v1 has no slot for the third player
+        assert!(room.join("third", ADDRESS).await.unwrap().is_some());
+        assert!(matches!(
+            room.configure(Some(1), None, None).await,
+            Err(Error::InvalidRoomConfig(_))
+        ));
+
+        room.claim_host("host", &room.host_token).await.unwrap();
+        room.host("host", HostAction::Start).await.unwrap();
+        assert!(matches!(
+            room.configure(Some(2), None, None).await,
+            Err(Error::MatchStarted)
+        ));
+    }
 
-    // Ensure a game exists for the room
-    guard
-        .room_game
-        .entry(room_key.to_string())
-        .or_insert_with(|| RoomGame::new());
+    /// The sessions the room has told to hang up so far.
+    fn disconnected(events: &mut broadcast::Receiver<Outgoing>) -> Vec<String> {
+        let mut disconnected = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Outgoing::Disconnect { session_id, .. } = event {
+                disconnected.push(session_id);
+            }
+        }
+        disconnected
+    }
 
-    // If loop already running, do nothing
-    if guard.room_tasks.contains_key(room_key) {
-        return;
+    #[tokio::test]
+    async fn only_the_host_runs_the_room() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        assert!(room.join("first", ADDRESS).await.unwrap().is_some());
+        assert!(room.join("second", other).await.unwrap().is_some());
+
+        assert!(matches!(
+            room.claim_host("first", "guess").await,
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            room.host("first", HostAction::Start).await,
+            Err(Error::Forbidden(_))
+        ));
+        room.claim_host("host", &room.host_token).await.unwrap();
+        assert_eq!(room.info().await.unwrap().host.as_deref(), Some("host"));
+
+        room.host("host", HostAction::Lock { locked: true })
+            .await
+            .unwrap();
+        assert!(matches!(
+            room.join("third", ADDRESS).await,
+            Err(Error::Forbidden(_))
+        ));
+        room.host("host", HostAction::Lock { locked: false })
+            .await
+            .unwrap();
+
+        // Kicked players may come back, banned ones may not resume
+        room.host("host", HostAction::Kick { player_id: 1 })
+            .await
+            .unwrap();
+        assert_eq!(disconnected(&mut events), vec!["second".to_string()]);
+        let (_, token) = room.join("second", other).await.unwrap().unwrap();
+        let ban = HostAction::Ban {
+            session_id: "second".to_string(),
+            by_address: false,
+        };
+        room.host("host", ban.clone()).await.unwrap();
+        assert_eq!(disconnected(&mut events), vec!["second".to_string()]);
+        assert_eq!(room.info().await.unwrap().players, 1);
+        assert!(matches!(
+            room.resume(&token, other).await,
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            room.host("host", ban).await,
+            Err(Error::PlayerNotFound)
+        ));
+
+        // Others at the same address are only refused when the host asks for it
+        assert!(room.join("third", other).await.unwrap().is_some());
+        let ban = HostAction::Ban {
+            session_id: "third".to_string(),
+            by_address: true,
+        };
+        room.host("host", ban).await.unwrap();
+        assert!(matches!(
+            room.join("again", other).await,
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            room.add_spectator("again", other).await,
+            Err(Error::Forbidden(_))
+        ));
+
+        room.host("host", HostAction::Start).await.unwrap();
+        room.host("host", HostAction::Restart).await.unwrap();
+        let details = room.details().await.unwrap();
+        assert_eq!(details.summary.phase, MatchPhase::Lobby);
+        assert_eq!(details.summary.info.players, 1);
     }
 
-    let state_cloned = Arc::clone(state);
-    let room_key_string = room_key.to_string();
-    let handle = tokio::spawn(async move {
-        let mut ticker = time::interval(Duration::from_millis(200));
-        loop {
-            ticker.tick().await; // every 200ms
-
-            // Build moves array from current room_state using room_players order
-            let (order, moves_snapshot) = {
-                let guard = state_cloned.read().unwrap(); // read lock is enough
-                let order = guard
-                    .room_players
-                    .get(&room_key_string)
-                    .cloned() // Vec<i32>
-                    .unwrap_or_default();
-
-                let moves_snapshot = guard
-                    .room_state
-                    .get(&room_key_string)
-                    .cloned() // HashMap<i32, Move>  (cheap: Move is Copy)
-                    .unwrap_or_default();
-
-                (order, moves_snapshot)
-            }; // <- guard dropped here, no references live
-
-            // Build fixed-size array outside the lock
-            let mut moves_arr = [Move::Stay; 4];
-            for (idx, pid) in order.iter().take(4).enumerate() {
-                if let Some(mv) = moves_snapshot.get(pid).copied() {
-                    moves_arr[idx] = mv;
-                }
-            }
+    #[tokio::test]
+    async fn hosting_passes_to_a_player_when_the_host_leaves() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let _events = room.subscribe().await.unwrap();
+        room.claim_host("host", &room.host_token).await.unwrap();
+        assert!(room.join("first", ADDRESS).await.unwrap().is_some());
+        let (_, token) = room.join("second", ADDRESS).await.unwrap().unwrap();
+        let host = async || room.info().await.unwrap().host;
+
+        room.release_host("host").await.unwrap();
+        assert_eq!(host().await.as_deref(), Some("first"));
+        room.host("first", HostAction::Transfer { player_id: 1 })
+            .await
+            .unwrap();
+        assert_eq!(host().await.as_deref(), Some("second"));
+
+        // Losing connection hands it on too, and the host token takes it back
+        room.suspend(&token, 0).await.unwrap();
+        assert_eq!(host().await.as_deref(), Some("first"));
+        room.claim_host("host", &room.host_token).await.unwrap();
+        assert_eq!(host().await.as_deref(), Some("host"));
+    }
 
-            // Re-lock to mutate the game and snapshot positions, check for score reset
-            let (positions_json_opt, should_reset_moves, players_to_reset_moves) = {
-                let mut guard = state_cloned.write().unwrap();
-                if let Some(game) = guard.room_game.get_mut(&room_key_string) {
-                    let old_scores = game.get_scores();
-                    let players_to_reset_moves = game.step(moves_arr);
-                    let new_scores = game.get_scores();
-
-                    // Check if score changed (someone scored)
-                    let score_changed = old_scores != new_scores;
-
-                    // Reset moves for players who were caught in enemy territory
-                    let positions = game.positions();
-                    let flag_captors = game.get_flag_captors(); // We need to add this method
-                    let payload = serde_json::json!({
-                        "type": "positions",
-                        "players": positions,
-                        "flag_captors": flag_captors,
-                        "scores": game.get_scores(), // We need to add this method too
-                    });
-                    (
-                        Some(payload.to_string()),
-                        score_changed,
-                        players_to_reset_moves,
-                    )
-                } else {
-                    (None, false, Vec::new())
-                }
-            };
-
-            // Reset all player moves to Stay if someone scored
-            if should_reset_moves {
-                let mut guard = state_cloned.write().unwrap();
-                if let Some(room_state) = guard.room_state.get_mut(&room_key_string) {
-                    for (_, player_move) in room_state.iter_mut() {
-                        *player_move = Move::Stay;
-                    }
-                }
-                println!("🔄 All player moves reset to Stay after score!");
-            }
+    #[tokio::test]
+    async fn resuming_takes_over_a_slot_still_held_open() {
+        let state = SharedState::default();
+        let room = create_room(&state, settings()).unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        let (_, token) = room.join("first", ADDRESS).await.unwrap().unwrap();
+
+        // The first socket has not noticed it dropped, so it is hung up on
+        let session = room.resume(&token, ADDRESS).await.unwrap().unwrap();
+        assert_eq!(session.disconnects(), 1);
+        assert_eq!(disconnected(&mut events), vec!["first".to_string()]);
+
+        // and its late hang-up leaves the new connection alone
+        assert_eq!(room.suspend(&token, 0).await.unwrap(), None);
+        assert!(room.expire(&token, 1).await.unwrap().is_none());
+        assert_eq!(room.suspend(&token, 1).await.unwrap(), Some(2));
+        assert!(room.expire(&token, 2).await.unwrap().is_some());
+    }
 
-            // Reset moves for players who were caught in enemy territory (outside the main lock)
-            if !players_to_reset_moves.is_empty() {
-                let mut guard = state_cloned.write().unwrap();
-
-                // First get the order mapping
-                let order = guard
-                    .room_players
-                    .get(&room_key_string)
-                    .cloned()
-                    .unwrap_or_default();
-
-                // Then reset moves for each player
-                for &player_index in &players_to_reset_moves {
-                    if let Some(&player_id) = order.get(player_index) {
-                        if let Some(room_state) = guard.room_state.get_mut(&room_key_string) {
-                            room_state.insert(player_id, Move::Stay);
-                            println!(
-                                "🔄 Reset move to Stay for player {} (index {})",
-                                player_id, player_index
-                            );
-                        }
-                    }
-                }
+    #[tokio::test]
+    async fn resumed_players_number_their_moves_afresh() {
+        let state = SharedState::default();
+        let room = create_room(
+            &state,
+            RoomSettings {
+                rules: GameRules {
+                    countdown_secs: Fixed::ZERO,
+                    ..GameRules::default()
+                },
+                ..settings()
+            },
+        )
+        .unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        room.claim_host("host", &room.host_token).await.unwrap();
+        let (player_id, token) = room.join("first", ADDRESS).await.unwrap().unwrap();
+        room.move_player(player_id, Move::new(1, 0), Some(40))
+            .await
+            .unwrap();
+
+        room.resume(&token, ADDRESS).await.unwrap().unwrap();
+        room.move_player(player_id, Move::new(0, 1), Some(1))
+            .await
+            .unwrap();
+        room.host("host", HostAction::Start).await.unwrap();
+
+        let positions = loop {
+            if let Ok(Outgoing::Snapshot(positions)) = events.recv().await {
+                break positions;
             }
+        };
+        assert_eq!(positions.input_seqs[0], Some(1));
+    }
 
-            // Broadcast after lock is released
-            if let Some(json) = positions_json_opt {
-                broadcast_to_room(&state_cloned, &room_key_string, &json);
+    #[tokio::test]
+    async fn players_steer_the_piece_of_their_player_id() {
+        let state = SharedState::default();
+        let room = create_room(
+            &state,
+            RoomSettings {
+                rules: GameRules {
+                    countdown_secs: Fixed::ZERO,
+                    ..GameRules::default()
+                },
+                ..settings()
+            },
+        )
+        .unwrap();
+        let mut events = room.subscribe().await.unwrap();
+        room.claim_host("host", &room.host_token).await.unwrap();
+        assert!(room.join("first", ADDRESS).await.unwrap().is_some());
+        assert!(room.join("second", ADDRESS).await.unwrap().is_some());
+
+        // The newcomer takes the free slot 0, after player 1 in join order
+        room.host("host", HostAction::Kick { player_id: 0 })
+            .await
+            .unwrap();
+        let (player_id, _) = room.join("third", ADDRESS).await.unwrap().unwrap();
+        assert_eq!(player_id, 0);
+        room.move_player(0, Move::new(1, 0), Some(7)).await.unwrap();
+        room.host("host", HostAction::Start).await.unwrap();
+
+        let positions = loop {
+            if let Ok(Outgoing::Snapshot(positions)) = events.recv().await {
+                break positions;
             }
-        }
-    });
-
-    guard.room_tasks.insert(room_key.to_string(), handle);
+        };
+        let spawns = GameState::from_map(&Map::classic(), 2).unwrap().positions();
+        assert_ne!(positions.players[0], spawns[0]);
+        assert_eq!(positions.players[1], spawns[1]);
+        assert_eq!(positions.input_seqs[..2], [Some(7), None]);
+    }
 }
@@ -0,0 +1,117 @@
+// Generates src/protocol/types.ts from the JSON Schema the backend publishes in
+// src/protocol/schema.json. Run it with `npm run generate:protocol` after
+// regenerating the schema.
+
+import { readFileSync, writeFileSync } from "node:fs";
+import { dirname, join } from "node:path";
+import { fileURLToPath } from "node:url";
+
+const root = join(dirname(fileURLToPath(import.meta.url)), "..");
+const schemaPath = join(root, "src/protocol/schema.json");
+const typesPath = join(root, "src/protocol/types.ts");
+
+const schema = JSON.parse(readFileSync(schemaPath, "utf8"));
+
+const indent = (depth) => "  ".repeat(depth);
+
+const comment = (description, depth) => {
+  if (!description) {
+    return "";
+  }
+  const lines = description.split("\n").map((line) => `${indent(depth)} *${line ? ` ${line}` : ""}`);
+  return `${indent(depth)}/**\n${lines.join("\n")}\n${indent(depth)} */\n`;
+};
+
+const union = (types) => {
+  const unique = [...new Set(types)];
+  return unique.length === 1 ? unique[0] : unique.join(" | ");
+};
+
+// Wraps unions and intersections so they can be used as array items or intersection parts
+const group = (type) => {
+  let nesting = 0;
+  for (const char of type) {
+    if ("{[(".includes(char)) {
+      nesting += 1;
+    } else if ("}])".includes(char)) {
+      nesting -= 1;
+    } else if (nesting === 0 && "|&".includes(char)) {
+      return `(${type})`;
+    }
+  }
+  return type;
+};
+
+const objectType = (node, depth) => {
+  const required = new Set(node.required ?? []);
+  const fields = Object.entries(node.properties ?? {}).map(
+    ([name, property]) =>
+      `${comment(property.description, depth + 1)}${indent(depth + 1)}${name}${
+        required.has(name) ? "" : "?"
+      }: ${toType(property, depth + 1)};`,
+  );
+  return fields.length ? `{\n${fields.join("\n")}\n${indent(depth)}}` : "{}";
+};
+
+const primitive = (name, node, depth) => {
+  switch (name) {
+    case "string":
+    case "boolean":
+    case "null":
+      return name;
+    case "integer":
+    case "number":
+      return "number";
+    case "array":
+      if (node.prefixItems) {
+        return `[${node.prefixItems.map((item) => toType(item, depth)).join(", ")}]`;
+      }
+      return `${group(node.items ? toType(node.items, depth) : "unknown")}[]`;
+    case "object":
+      return objectType(node, depth);
+    default:
+      throw new Error(`unsupported schema type ${name}`);
+  }
+};
+
+function toType(node, depth = 0) {
+  if (node === true || node === undefined) {
+    return "unknown";
+  }
+  if ("const" in node) {
+    return JSON.stringify(node.const);
+  }
+  if (node.enum) {
+    return union(node.enum.map((value) => JSON.stringify(value)));
+  }
+  const parts = [];
+  if (node.$ref) {
+    parts.push(node.$ref.replace("#/$defs/", ""));
+  }
+  if (node.properties || (node.type === "object" && !node.$ref && !node.oneOf)) {
+    parts.push(objectType(node, depth));
+  } else if (node.type && node.type !== "object") {
+    const names = Array.isArray(node.type) ? node.type : [node.type];
+    parts.push(union(names.map((name) => primitive(name, node, depth))));
+  }
+  const variants = node.oneOf ?? node.anyOf;
+  if (variants) {
+    parts.push(union(variants.map((variant) => toType(variant, depth))));
+  }
+  if (parts.length === 0) {
+    return "unknown";
+  }
+  return parts.length === 1 ? parts[0] : parts.map(group).join(" & ");
+}
+
+const definitions = Object.entries(schema.$defs).map(
+  ([name, definition]) => `${comment(definition.description, 0)}export type ${name} = ${toType(definition)};\n`,
+);
+
+const header = `// Generated from schema.json by scripts/generate-protocol.mjs. Do not edit by hand,
+// run \`npm run generate:protocol\` instead.
+
+export const PROTOCOL_VERSION = ${schema.version};
+`;
+
+writeFileSync(typesPath, [header, ...definitions].join("\n"));
@@ -15,6 +15,7 @@ interface GameState {
   players: Array<{ x: number; y: number; team: number }>;
   walls: Array<{ x: number; y: number }>;
   flags: Array<{ x: number; y: number; team: number; captured: boolean }>;
+  flagCaptors: [number | null, number | null];
   scores: [number, number];
   isStarted: boolean;
   connectedPlayers: number;
@@ -41,7 +42,10 @@ const Host = ({ gameId }: Props) => {
   });
 
   const gameKey = currentGameInfo?.room_key;
-  const wsUrl = gameKey ? `ws://localhost:8000/rooms/${gameKey}?role=host` : "";
+  const hostToken = currentGameInfo?.host_token ?? "";
+  const wsUrl = gameKey
+    ? `ws://localhost:8000/rooms/${gameKey}?role=host&host_token=${encodeURIComponent(hostToken)}`
+    : "";
 
   const { isConnected, sendMessage, connect, connectionState } = useWebSocket({
     url: wsUrl,
@@ -64,23 +68,22 @@ const Host = ({ gameId }: Props) => {
             connectedPlayers: Math.max(0, prev.connectedPlayers - 1)
           }));
           break;
-        case "positions":
-          if (message.players && Array.isArray(message.players)) {
-            const players = message.players.map((pos: [number, number], index: number) => ({
-              x: Math.floor(pos[0]),
-              y: Math.floor(pos[1]),
-              team: index % 2
-            }));
-            const flagCaptors = message.flag_captors || [null, null];
-            const scores = message.scores || [0, 0];
-            setGameState(prev => ({ 
-              ...prev, 
-              players,
-              flagCaptors,
-              scores
-            }));
-          }
+        case "positions": {
+          const players = message.players.map((pos, index) => ({
+            x: Math.floor(pos[0]),
+            y: Math.floor(pos[1]),
+            team: index % 2
+          }));
+          const flagCaptors: GameState["flagCaptors"] = [message.flag_captors[0] ?? null, message.flag_captors[1] ?? null];
+          const scores: GameState["scores"] = [message.scores[0] ?? 0, message.scores[1] ?? 0];
+          setGameState(prev => ({ 
+            ...prev, 
+            players,
+            flagCaptors,
+            scores
+          }));
           break;
+        }
         case "game_started":
           setGameState(prev => ({ ...prev, isStarted: true }));
           break;
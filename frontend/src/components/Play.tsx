@@ -72,23 +72,22 @@ const Play = ({ gameId }: Props) => {
             return prev;
           });
           break;
-        case "positions":
-          if (message.players && Array.isArray(message.players)) {
-            const players = message.players.map((pos: [number, number], index: number) => ({
-              x: Math.floor(pos[0]),
-              y: Math.floor(pos[1]),
-              team: index % 2
-            }));
-            const flagCaptors = message.flag_captors || [null, null];
-            const scores = message.scores || [0, 0];
-            setGameState(prev => ({ 
-              ...prev, 
-              players, 
-              flagCaptors,
-              scores
-            }));
-          }
+        case "positions": {
+          const players = message.players.map((pos, index) => ({
+            x: Math.floor(pos[0]),
+            y: Math.floor(pos[1]),
+            team: index % 2
+          }));
+          const flagCaptors: GameState["flagCaptors"] = [message.flag_captors[0] ?? null, message.flag_captors[1] ?? null];
+          const scores: GameState["scores"] = [message.scores[0] ?? 0, message.scores[1] ?? 0];
+          setGameState(prev => ({ 
+            ...prev, 
+            players, 
+            flagCaptors,
+            scores
+          }));
           break;
+        }
         case "game_started":
           setGameState(prev => ({ ...prev, isStarted: true }));
           break;
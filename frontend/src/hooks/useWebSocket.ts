@@ -1,15 +1,11 @@
 "use client";
 
+import type { ClientEvent, ServerEvent } from "@/protocol/types";
 import { useEffect, useRef, useState } from "react";
 
-interface WebSocketMessage {
-  type: string;
-  [key: string]: any;
-}
-
 interface UseWebSocketProps {
   url: string;
-  onMessage?: (message: WebSocketMessage) => void;
+  onMessage?: (message: ServerEvent) => void;
   onOpen?: () => void;
   onClose?: () => void;
   onError?: (error: Event) => void;
@@ -21,7 +17,7 @@ export const useWebSocket = ({ url, onMessage, onOpen, onClose, onError }: UseWe
   const wsRef = useRef<WebSocket | null>(null);
   const urlRef = useRef<string>('');
 
-  const sendMessage = (message: any) => {
+  const sendMessage = (message: ClientEvent) => {
     if (wsRef.current && wsRef.current.readyState === WebSocket.OPEN) {
       wsRef.current.send(JSON.stringify(message));
     }
@@ -50,7 +46,7 @@ export const useWebSocket = ({ url, onMessage, onOpen, onClose, onError }: UseWe
 
     wsRef.current.onmessage = (event) => {
       try {
-        const message = JSON.parse(event.data);
+        const message: ServerEvent = JSON.parse(event.data);
         onMessage?.(message);
       } catch (error) {
         console.error('Failed to parse WebSocket message:', error);
@@ -0,0 +1,415 @@
+// Generated from schema.json by scripts/generate-protocol.mjs. Do not edit by hand,
+// run `npm run generate:protocol` instead.
+
+export const PROTOCOL_VERSION = 1;
+
+export type ClientEvent = {
+  type: "start_game";
+} | {
+  player_id: number;
+  type: "kick_player";
+} | {
+  by_address?: boolean;
+  session_id: string;
+  type: "ban_session";
+} | {
+  locked: boolean;
+  type: "lock_room";
+} | {
+  type: "restart_match";
+} | {
+  player_id: number;
+  type: "transfer_host";
+} | {
+  content: string;
+  type: "chat";
+} | {
+  dx: number;
+  dy: number;
+  /**
+   * Numbers the client's moves, counting up. Numbered moves are applied one per
+   * tick in order, and `input_seqs` in `positions` says which was applied last.
+   * Moves without a number replace whatever the player was doing at once.
+   */
+  seq?: number | null;
+  type: "move";
+} | {
+  snapshot: number;
+  type: "ack";
+};
+
+/**
+ * How `positions` frames are sent, chosen with the `encoding` query parameter.
+ */
+export type Encoding = "json" | "msgpack";
+
+/**
+ * A number of tiles or seconds, rounded to the nearest thousandth.
+ */
+export type Fixed = number;
+
+/**
+ * Where a team's flag is, as sent to clients.
+ */
+export type FlagStatus = {
+  team: number;
+  x: number;
+  y: number;
+} & ({
+  state: "at_base";
+} | {
+  by: number;
+  state: "carried";
+} | {
+  state: "dropped";
+  ticks_until_return: number;
+});
+
+/**
+ * Something that happened during a [`GameState::step`](super::GameState::step),
+ * in the order it happened. Player and team values are indices into the game.
+ */
+export type GameEvent = {
+  player: number;
+  type: "player_moved";
+  x: number;
+  y: number;
+} | {
+  by: number;
+  player: number;
+  type: "player_tagged";
+} | {
+  jail_team: number;
+  player: number;
+  type: "player_jailed";
+} | {
+  by: number;
+  player: number;
+  type: "player_rescued";
+} | {
+  player: number;
+  type: "player_respawned";
+} | {
+  player: number;
+  team: number;
+  type: "flag_picked";
+} | {
+  player: number;
+  team: number;
+  type: "flag_dropped";
+  x: number;
+  y: number;
+} | {
+  by?: number | null;
+  team: number;
+  type: "flag_returned";
+} | {
+  player: number;
+  team: number;
+  type: "scored";
+} | {
+  type: "round_reset";
+} | {
+  phase: MatchPhase;
+  type: "phase_changed";
+} | {
+  type: "overtime_started";
+} | {
+  overtime: boolean;
+  scores: number[];
+  type: "match_over";
+  winner: number;
+};
+
+/**
+ * House rules for a game. Every field has a default, so hosts only need to send
+ * the ones they want to change. Sizes and distances are in tiles and durations in
+ * seconds, both rounded to the nearest thousandth. Speeds and durations do not
+ * depend on `tick_rate`, so the game plays the same at any rate.
+ */
+export type GameRules = {
+  /**
+   * Whether diagonal moves are allowed. When they are not, diagonal moves are ignored.
+   */
+  allow_diagonals?: boolean;
+  /**
+   * Largest distance between player and flag centers that still touches the flag.
+   */
+  capture_distance?: Fixed;
+  /**
+   * Seconds players wait at their spawns before each round.
+   */
+  countdown_secs?: Fixed;
+  /**
+   * Seconds a dropped flag lies untouched before returning to its base.
+   */
+  flag_return_secs?: Fixed;
+  /**
+   * Side length of a flag's square hitbox.
+   */
+  flag_size?: Fixed;
+  /**
+   * Side length of a player's square hitbox.
+   */
+  player_size?: Fixed;
+  /**
+   * Tiles a player moves per second.
+   */
+  player_speed?: Fixed;
+  /**
+   * Seconds a tagged player sits out of play at their spawn.
+   */
+  respawn_delay_secs?: Fixed;
+  /**
+   * Seconds between a score and the next countdown.
+   */
+  round_over_secs?: Fixed;
+  /**
+   * The match is won by the first team to reach this score.
+   */
+  score_limit?: number | null;
+  /**
+   * Seconds after respawning during which a player cannot be tagged.
+   */
+  spawn_protection_secs?: Fixed;
+  tag_behavior?: TagBehavior;
+  /**
+   * Simulation steps per second.
+   */
+  tick_rate?: number;
+  /**
+   * Seconds of play before the leading team wins. A tie goes to sudden-death overtime.
+   */
+  time_limit_secs?: Fixed | null;
+};
+
+/**
+ * Where a match is in its lifecycle. Players can only move while `Playing`.
+ *
+ * `Lobby -> Countdown -> Playing -> RoundOver -> Countdown -> ... -> MatchOver`
+ */
+export type MatchPhase = {
+  name: "lobby";
+} | {
+  name: "countdown";
+  ticks_left: number;
+} | {
+  name: "playing";
+} | {
+  name: "round_over";
+  ticks_left: number;
+} | {
+  name: "match_over";
+};
+
+/**
+ * Controls replay playback on a `role=replay` connection.
+ */
+export type PlaybackCommand = {
+  type: "pause";
+} | {
+  type: "play";
+} | {
+  tick: number;
+  type: "seek";
+} | {
+  speed: number;
+  type: "speed";
+};
+
+/**
+ * Everything clients need to draw the game. Per-player lists are in player order
+ * and per-team lists in team order.
+ */
+export type Positions = {
+  /**
+   * Who carries each team's flag.
+   */
+  flag_captors: (number | null)[];
+  flags: FlagStatus[];
+  /**
+   * The `seq` of the last `move` applied for each player, for clients predicting
+   * their own movement. Left out of replays.
+   */
+  input_seqs: (number | null)[];
+  jailed: boolean[];
+  /**
+   * Each team's jail cell, empty on maps without jails.
+   */
+  jails: [number, number][];
+  overtime: boolean;
+  phase: MatchPhase;
+  /**
+   * Top-left corner of each player, in tiles.
+   */
+  players: [number, number][];
+  protection_ticks: number[];
+  remaining_ticks?: number | null;
+  respawn_ticks: number[];
+  scores: number[];
+  /**
+   * Counts up by one with every frame of the match.
+   */
+  snapshot: number;
+};
+
+/**
+ * A [`Positions`] frame as the changes from an earlier one. Fields that did not
+ * change are left out.
+ */
+export type PositionsDelta = {
+  /**
+   * The snapshot the changes apply to.
+   */
+  base: number;
+  flag_captors?: (number | null)[] | null;
+  /**
+   * Flags that moved or changed state.
+   */
+  flags: FlagStatus[];
+  input_seqs?: (number | null)[] | null;
+  jailed?: boolean[] | null;
+  overtime?: boolean | null;
+  phase?: MatchPhase | null;
+  /**
+   * Players that moved, as `[player, x, y]`.
+   */
+  players: [number, number, number][];
+  protection_ticks?: number[] | null;
+  /**
+   * `null` once the match no longer has a time limit.
+   */
+  remaining_ticks?: number | null;
+  respawn_ticks?: number[] | null;
+  scores?: number[] | null;
+  snapshot: number;
+};
+
+/**
+ * What a connection may do in a room, chosen with the `role` query parameter.
+ */
+export type Role = "host" | "player" | "spectator" | "replay";
+
+/**
+ * Who is in a room.
+ */
+export type RoomInfo = {
+  /**
+   * Session id of the room's host, if it has one.
+   */
+  host?: string | null;
+  /**
+   * Whether new players are kept out.
+   */
+  locked: boolean;
+  max_players: number;
+  players: number;
+  room_key: string;
+  /**
+   * `positions` frames sent per second.
+   */
+  snapshot_rate: number;
+  spectators: number;
+  /**
+   * Simulation steps per second.
+   */
+  tick_rate: number;
+};
+
+export type ServerEvent = {
+  encoding: Encoding;
+  protocol_version: number;
+  /**
+   * Lets a player reclaim their slot with `?resume=<token>` after losing connection.
+   */
+  resume_token?: string | null;
+  role: Role;
+  room: string;
+  room_info?: RoomInfo | null;
+  session_id: string;
+  type: "welcome";
+} | {
+  player_id: number;
+  session_id: string;
+  type: "user_joined";
+} | {
+  session_id: string;
+  type: "user_left";
+} | {
+  player_id: number;
+  session_id: string;
+  type: "user_disconnected";
+} | {
+  player_id: number;
+  session_id: string;
+  type: "user_rejoined";
+} | {
+  session_id: string;
+  type: "host_left";
+} | {
+  player_id?: number | null;
+  session_id: string;
+  type: "host_changed";
+} | {
+  banned: boolean;
+  player_id?: number | null;
+  session_id: string;
+  type: "user_kicked";
+} | {
+  locked: boolean;
+  type: "room_locked";
+} | {
+  restarted_by: string;
+  type: "match_restarted";
+} | {
+  session_id: string;
+  spectators: number;
+  type: "spectator_joined";
+} | {
+  session_id: string;
+  spectators: number;
+  type: "spectator_left";
+} | {
+  room_info: RoomInfo;
+  rules: GameRules;
+  type: "room_updated";
+} | {
+  started_by: string;
+  type: "game_started";
+} | {
+  content: string;
+  from: string;
+  type: "chat";
+} | Positions & {
+  type: "positions";
+} | PositionsDelta & {
+  type: "positions_delta";
+} | {
+  events: GameEvent[];
+  type: "game_events";
+} | {
+  overtime: boolean;
+  scores: number[];
+  type: "match_over";
+  winner: number;
+  winner_name?: string | null;
+} | {
+  finished: boolean;
+  /**
+   * Whether playback ended in the recorded state, once it has finished.
+   */
+  matches_recording?: boolean | null;
+  paused: boolean;
+  speed: number;
+  tick: number;
+  tick_count: number;
+  type: "replay_state";
+} | {
+  message: string;
+  type: "error";
+};
+
+/**
+ * What happens when a player is caught in enemy territory.
+ */
+export type TagBehavior = "respawn" | "jail" | "off";
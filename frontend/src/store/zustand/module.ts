@@ -40,8 +40,12 @@ export const useGameStore = create<GameStore>()(
         },
 
         fetchGame: async (key: string) => {
-          // For fetching, we just need to set the room key
-          const gameInfo: GameInfo = { room_key: key };
+          // For fetching, we just need to set the room key, keeping the host token
+          // if this is the room we created
+          const current = get().currentGameInfo;
+          const host_token =
+            current?.room_key === key ? current.host_token : undefined;
+          const gameInfo: GameInfo = { room_key: key, host_token };
           set({ currentGameInfo: gameInfo });
           return gameInfo;
         },
@@ -7,6 +7,8 @@ export interface GameStoreData {
 
 export interface GameInfo {
   room_key: string;
+  // Only known to whoever created the room; needed to connect as its host
+  host_token?: string;
 }
 
 export interface GameStoreActions {